@@ -0,0 +1,3 @@
+fn main() {
+    println!("demo {}", std::env::current_dir().unwrap().display());
+}
@@ -0,0 +1,325 @@
+use crate::entry::Entry;
+use crate::paths::normalize;
+use crate::IntegrationTestEnvironment;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::{Archive, EntryType};
+
+pub(crate) enum Format {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+fn format(archive: &Path) -> io::Result<Format> {
+    let name = archive
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name.ends_with(".tar") {
+        Ok(Format::Tar)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Ok(Format::TarGz)
+    } else if name.ends_with(".zip") {
+        Ok(Format::Zip)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "unknown archive format {:?}, expected .tar, .tar.gz or .zip",
+                archive
+            ),
+        ))
+    }
+}
+
+pub(crate) fn member_path(path: &Path) -> io::Result<PathBuf> {
+    normalize(path).map_err(|reason| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("archive member {:?}: {}", path, reason),
+        )
+    })
+}
+
+// A directory has no content.
+struct Member {
+    path: PathBuf,
+    content: Option<Vec<u8>>,
+    mode: u32,
+}
+
+impl IntegrationTestEnvironment {
+    // Nothing is staged unless every member of the archive has been read.
+    pub fn add_archive<S, D>(&mut self, archive: S, dest: D) -> io::Result<()>
+    where
+        S: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        let archive = archive.as_ref();
+        let dest = member_path(dest.as_ref())?;
+        let file = File::open(archive)?;
+        let members = match format(archive)? {
+            Format::Tar => read_tar(file)?,
+            Format::TarGz => read_tar(flate2::read::GzDecoder::new(file))?,
+            Format::Zip => read_zip(file)?,
+        };
+        for member in members {
+            self.add_archive_member(dest.join(member.path), member.content, member.mode);
+        }
+        Ok(())
+    }
+
+    fn add_archive_member(&mut self, path: PathBuf, content: Option<Vec<u8>>, mode: u32) {
+        match content {
+            Some(content) => {
+                if mode & 0o111 != 0 {
+                    self.modes.insert(path.clone(), mode & 0o7777);
+                }
+                self.stage(path, Entry::Bytes(content));
+            }
+            None => {
+                self.stage(path, Entry::Dir);
+            }
+        }
+    }
+}
+
+fn read_tar<R: Read>(reader: R) -> io::Result<Vec<Member>> {
+    let mut members = vec![];
+    let mut archive = Archive::new(reader);
+    for member in archive.entries()? {
+        let mut member = member?;
+        let path = member_path(&member.path()?)?;
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let mode = member.header().mode()?;
+        let content = match member.header().entry_type() {
+            EntryType::Regular | EntryType::Continuous => {
+                let mut content = vec![];
+                member.read_to_end(&mut content)?;
+                Some(content)
+            }
+            EntryType::Directory => None,
+            entry_type => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "archive member {:?} has unsupported type {:?}",
+                        path, entry_type
+                    ),
+                ))
+            }
+        };
+        members.push(Member {
+            path,
+            content,
+            mode,
+        });
+    }
+    Ok(members)
+}
+
+fn read_zip(file: File) -> io::Result<Vec<Member>> {
+    let mut members = vec![];
+    let mut archive = zip::ZipArchive::new(file)?;
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index)?;
+        let path = member_path(Path::new(member.name()))?;
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let mode = member.unix_mode().unwrap_or(0o644);
+        let content = if member.is_dir() {
+            None
+        } else if member.is_file() {
+            let mut content = vec![];
+            member.read_to_end(&mut content)?;
+            Some(content)
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("archive member {:?} has an unsupported type", path),
+            ));
+        };
+        members.push(Member {
+            path,
+            content,
+            mode,
+        });
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::fs::File;
+    use std::io::Write;
+    use std::path::{Path, PathBuf};
+    use tar::{Builder, EntryType, Header};
+    use tempdir::TempDir;
+
+    fn header(entry_type: EntryType, size: u64, mode: u32) -> Header {
+        let mut header = Header::new_gnu();
+        header.set_entry_type(entry_type);
+        header.set_size(size);
+        header.set_mode(mode);
+        header
+    }
+
+    fn build_tar<W: Write>(writer: W, with_symlink: bool) {
+        let mut builder = Builder::new(writer);
+        let mut dir = header(EntryType::Directory, 0, 0o755);
+        builder
+            .append_data(&mut dir, "empty_dir/", &[][..])
+            .unwrap();
+        let mut file = header(EntryType::Regular, 6, 0o644);
+        builder
+            .append_data(&mut file, "dir/file1", &b"test 1"[..])
+            .unwrap();
+        let mut script = header(EntryType::Regular, 9, 0o755);
+        builder
+            .append_data(&mut script, "bin/run.sh", &b"echo test"[..])
+            .unwrap();
+        if with_symlink {
+            let mut link = header(EntryType::Symlink, 0, 0o777);
+            builder.append_link(&mut link, "link", "dir/file1").unwrap();
+        }
+        builder.into_inner().unwrap().flush().unwrap();
+    }
+
+    fn build_tar_file(path: &Path, with_symlink: bool) {
+        build_tar(File::create(path).unwrap(), with_symlink);
+    }
+
+    fn assert_extracted(e: &IntegrationTestEnvironment, dest: &str) {
+        let dest = Path::new(dest);
+        let tree = e.tree();
+        for path in ["empty_dir", "dir/file1", "bin/run.sh"] {
+            assert!(
+                tree.contains(&dest.join(path)),
+                "{:?} not in {:?}",
+                path,
+                tree
+            );
+        }
+        assert_eq!(e.read_file(dest.join("dir/file1")), "test 1");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &str| {
+                e.path()
+                    .unwrap()
+                    .join(dest.join(path))
+                    .metadata()
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o777
+            };
+            assert_eq!(mode("bin/run.sh"), 0o755);
+            assert_eq!(mode("dir/file1") & 0o111, 0);
+        }
+    }
+
+    #[test]
+    fn add_archive_tar() {
+        let dir = TempDir::new("archive").unwrap();
+        let archive = dir.path().join("fixture.tar");
+        build_tar_file(&archive, false);
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_archive(&archive, "").unwrap();
+        e.setup();
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("bin"),
+                PathBuf::from("bin/run.sh"),
+                PathBuf::from("dir"),
+                PathBuf::from("dir/file1"),
+                PathBuf::from("empty_dir"),
+            ]
+        );
+        assert_extracted(&e, "");
+    }
+
+    #[test]
+    fn add_archive_rejects_symlink() {
+        let dir = TempDir::new("archive").unwrap();
+        let archive = dir.path().join("fixture.tar");
+        build_tar_file(&archive, true);
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        let error = e.add_archive(&archive, "").unwrap_err();
+        assert!(error.to_string().contains("unsupported type Symlink"));
+        assert!(e.is_empty());
+    }
+
+    #[test]
+    fn add_archive_rejects_path_traversal() {
+        let dir = TempDir::new("archive").unwrap();
+        let archive = dir.path().join("evil.tar");
+        let mut builder = Builder::new(File::create(&archive).unwrap());
+        let mut file = header(EntryType::Regular, 4, 0o644);
+        // `append_data` refuses `..`, so the name is written in the header directly.
+        file.as_old_mut().name[..7].copy_from_slice(b"../evil");
+        file.set_cksum();
+        builder.append(&file, &b"evil"[..]).unwrap();
+        builder.finish().unwrap();
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        let error = e.add_archive(&archive, "").unwrap_err();
+        assert!(error.to_string().contains("escaping the environment root"));
+        assert!(e.is_empty());
+    }
+
+    #[test]
+    fn add_archive_tar_gz() {
+        use flate2::write::GzEncoder;
+        use flate2::Compression;
+
+        let dir = TempDir::new("archive").unwrap();
+        let archive = dir.path().join("fixture.tar.gz");
+        build_tar(
+            GzEncoder::new(File::create(&archive).unwrap(), Compression::default()),
+            false,
+        );
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_archive(&archive, "vendor").unwrap();
+        e.setup();
+        assert_extracted(&e, "vendor");
+    }
+
+    #[test]
+    fn add_archive_zip() {
+        use zip::write::SimpleFileOptions;
+        use zip::ZipWriter;
+
+        let dir = TempDir::new("archive").unwrap();
+        let archive = dir.path().join("fixture.zip");
+        let mut writer = ZipWriter::new(File::create(&archive).unwrap());
+        let options = SimpleFileOptions::default();
+        writer.add_directory("empty_dir/", options).unwrap();
+        writer
+            .start_file("dir/file1", options.unix_permissions(0o644))
+            .unwrap();
+        writer.write_all(b"test 1").unwrap();
+        writer
+            .start_file("bin/run.sh", options.unix_permissions(0o755))
+            .unwrap();
+        writer.write_all(b"echo test").unwrap();
+        writer.finish().unwrap();
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_archive(&archive, "vendor").unwrap();
+        e.setup();
+        assert_extracted(&e, "vendor");
+    }
+}
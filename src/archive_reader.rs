@@ -0,0 +1,201 @@
+use crate::archive::{member_path, Format};
+use crate::IntegrationTestEnvironment;
+use std::collections::HashMap;
+use std::fs::read;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tar::Archive;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveEntry {
+    pub path: PathBuf,
+    pub size: u64,
+    pub mode: u32,
+    pub is_dir: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveContents {
+    entries: Vec<ArchiveEntry>,
+    contents: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl ArchiveContents {
+    pub fn entries(&self) -> &[ArchiveEntry] {
+        &self.entries
+    }
+
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.entries
+            .iter()
+            .map(|entry| entry.path.clone())
+            .collect()
+    }
+
+    pub fn read_entry<P: AsRef<Path>>(&self, path: P) -> Vec<u8> {
+        self.contents
+            .get(path.as_ref())
+            .cloned()
+            .unwrap_or_else(|| panic!("archive has no file entry {:?}", path.as_ref()))
+    }
+
+    pub fn read_entry_string<P: AsRef<Path>>(&self, path: P) -> String {
+        String::from_utf8(self.read_entry(path.as_ref()))
+            .unwrap_or_else(|_| panic!("archive entry {:?} is not valid UTF-8", path.as_ref()))
+    }
+
+    fn push(&mut self, path: PathBuf, mode: u32, content: Option<Vec<u8>>) {
+        let is_dir = content.is_none();
+        let size = content.as_ref().map_or(0, |content| content.len() as u64);
+        if let Some(content) = content {
+            self.contents.insert(path.clone(), content);
+        }
+        self.entries.push(ArchiveEntry {
+            path,
+            size,
+            mode,
+            is_dir,
+        });
+    }
+}
+
+fn sniff(content: &[u8]) -> Option<Format> {
+    if content.starts_with(b"PK\x03\x04") || content.starts_with(b"PK\x05\x06") {
+        Some(Format::Zip)
+    } else if content.starts_with(&[0x1f, 0x8b]) {
+        Some(Format::TarGz)
+    } else if content.len() > 262 && &content[257..262] == b"ustar" {
+        Some(Format::Tar)
+    } else {
+        None
+    }
+}
+
+fn read_tar<R: Read>(reader: R) -> io::Result<ArchiveContents> {
+    let mut contents = ArchiveContents::default();
+    let mut archive = Archive::new(reader);
+    for member in archive.entries()? {
+        let mut member = member?;
+        let path = member_path(&member.path()?)?;
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let mode = member.header().mode()?;
+        if member.header().entry_type().is_dir() {
+            contents.push(path, mode, None);
+        } else {
+            let mut content = vec![];
+            member.read_to_end(&mut content)?;
+            contents.push(path, mode, Some(content));
+        }
+    }
+    Ok(contents)
+}
+
+fn read_zip(content: &[u8]) -> io::Result<ArchiveContents> {
+    let mut contents = ArchiveContents::default();
+    let mut archive = zip::ZipArchive::new(io::Cursor::new(content))?;
+    for index in 0..archive.len() {
+        let mut member = archive.by_index(index)?;
+        let path = member_path(Path::new(member.name()))?;
+        if path.as_os_str().is_empty() {
+            continue;
+        }
+        let mode = member.unix_mode().unwrap_or(0o644);
+        if member.is_dir() {
+            contents.push(path, mode, None);
+        } else {
+            let mut content = vec![];
+            member.read_to_end(&mut content)?;
+            contents.push(path, mode, Some(content));
+        }
+    }
+    Ok(contents)
+}
+
+impl IntegrationTestEnvironment {
+    pub fn read_archive<P: AsRef<Path>>(&self, path: P) -> ArchiveContents {
+        let path = self.live_path(path.as_ref(), "read archive");
+        let content = read(&path).unwrap_or_else(|_| panic!("fail to read archive {:?}", path));
+        let contents = match sniff(&content) {
+            Some(Format::Zip) => read_zip(&content),
+            Some(Format::TarGz) => read_tar(flate2::read::GzDecoder::new(content.as_slice())),
+            Some(Format::Tar) => read_tar(content.as_slice()),
+            None => panic!("{:?} is not a zip, tar or tar.gz archive", path),
+        };
+        contents.unwrap_or_else(|error| panic!("fail to read archive {:?}: {}", path, error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::fs::File;
+    use std::io::Write;
+    #[cfg(unix)]
+    use std::path::Path;
+    use std::path::PathBuf;
+
+    #[test]
+    #[cfg(unix)]
+    fn read_archive() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("src/dir/file1", "test 1");
+        e.add_file("src/run.sh", "echo test");
+        e.add_dir("src/empty_dir");
+        e.setup();
+        e.set_exec_permission("src/run.sh").unwrap();
+        let status = std::process::Command::new("tar")
+            .args(["-czf", "bundle", "-C", "src", "dir", "run.sh", "empty_dir"])
+            .current_dir(e.path().unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let archive = e.read_archive("bundle");
+        let mut paths = archive.paths();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("dir"),
+                PathBuf::from("dir/file1"),
+                PathBuf::from("empty_dir"),
+                PathBuf::from("run.sh"),
+            ]
+        );
+        let script = archive
+            .entries()
+            .iter()
+            .find(|entry| entry.path == Path::new("run.sh"))
+            .unwrap();
+        assert_eq!(script.size, 9);
+        assert_eq!(script.mode & 0o777, 0o755);
+        assert!(!script.is_dir);
+        assert_eq!(archive.read_entry_string("dir/file1"), "test 1");
+        assert_eq!(archive.read_entry("run.sh"), b"echo test");
+    }
+
+    #[test]
+    fn read_archive_zip() {
+        use zip::write::SimpleFileOptions;
+        use zip::ZipWriter;
+
+        let e = IntegrationTestEnvironment::new("test");
+        let mut writer = ZipWriter::new(File::create(e.path().unwrap().join("out")).unwrap());
+        writer
+            .add_directory("./", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .start_file("data.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"zipped").unwrap();
+        writer.finish().unwrap();
+
+        let archive = e.read_archive("out");
+        assert_eq!(archive.paths(), vec![PathBuf::from("data.txt")]);
+        assert_eq!(archive.entries()[0].size, 6);
+        assert_eq!(archive.read_entry_string("data.txt"), "zipped");
+    }
+}
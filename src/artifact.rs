@@ -0,0 +1,45 @@
+use crate::IntegrationTestEnvironment;
+use assert_cmd::Command;
+use std::env;
+use std::path::PathBuf;
+
+// Same lookup as `assert_cmd`: the test executable lives in `target/<profile>/deps`.
+fn target_dir() -> PathBuf {
+    let mut path = env::current_exe().expect("fail to find the test executable");
+    path.pop();
+    if path.ends_with("deps") {
+        path.pop();
+    }
+    path
+}
+
+fn artifact(path: PathBuf, build: String) -> PathBuf {
+    if !path.is_file() {
+        panic!("fail to find {:?}, build it with `{}`", path, build);
+    }
+    path
+}
+
+impl IntegrationTestEnvironment {
+    pub fn command_example<E: AsRef<str>>(&self, example: E) -> Command {
+        let example = example.as_ref();
+        let path =
+            target_dir()
+                .join("examples")
+                .join(format!("{}{}", example, env::consts::EXE_SUFFIX));
+        let path = artifact(path, format!("cargo build --example {}", example));
+        self.configure_command(example, Command::new(path))
+    }
+
+    // Workspace members share the target directory, the package only names the build
+    // command suggested when the binary is missing.
+    pub fn command_bin_of<P: AsRef<str>, B: AsRef<str>>(&self, package: P, bin: B) -> Command {
+        let bin = bin.as_ref();
+        let path = target_dir().join(format!("{}{}", bin, env::consts::EXE_SUFFIX));
+        let path = artifact(
+            path,
+            format!("cargo build -p {} --bin {}", package.as_ref(), bin),
+        );
+        self.configure_command(bin, Command::new(path))
+    }
+}
@@ -0,0 +1,313 @@
+use crate::invocation::invocation_line;
+use crate::{display_path, format_output, CommandOutcome, IntegrationTestEnvironment};
+use predicates::Predicate;
+use predicates_tree::CaseTreeExt;
+use std::collections::BTreeSet;
+use std::fs::read;
+use std::path::Path;
+use std::process::{ExitStatus, Output};
+
+pub(crate) fn describe_status(status: &ExitStatus) -> String {
+    if let Some(code) = status.code() {
+        return format!("exit code {}", code);
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return format!("terminated by signal {}", signal);
+        }
+    }
+    "terminated without exit code".to_string()
+}
+
+pub(crate) fn failure_report<P, T>(pred: &P, value: &T) -> String
+where
+    P: Predicate<T>,
+    T: ?Sized,
+{
+    pred.find_case(false, value)
+        .map(|case| case.tree().to_string())
+        .unwrap_or_else(|| pred.to_string())
+}
+
+impl IntegrationTestEnvironment {
+    pub fn assert_file<P: AsRef<Path>>(&self, path: P, pred: impl Predicate<str>) {
+        let content = self.read_asserted_file(path.as_ref());
+        let content = String::from_utf8_lossy(&content);
+        if !pred.eval(&content) {
+            panic!(
+                "assertion failed on file {:?}\n{}\n---------------------------\n{}",
+                path.as_ref(),
+                failure_report(&pred, content.as_ref()),
+                content
+            );
+        }
+    }
+
+    pub fn assert_file_bytes<P: AsRef<Path>>(&self, path: P, pred: impl Predicate<[u8]>) {
+        let content = self.read_asserted_file(path.as_ref());
+        if !pred.eval(&content) {
+            panic!(
+                "assertion failed on file {:?}\n{}\n---------------------------\n{:?}",
+                path.as_ref(),
+                failure_report(&pred, content.as_slice()),
+                content
+            );
+        }
+    }
+
+    pub fn assert_code<C: AsRef<str>>(&self, crate_name: C, args: &[&str], expected: i32) {
+        let output = self
+            .command_args(crate_name.as_ref(), args)
+            .output()
+            .unwrap_or_else(|_| panic!("fail to run {:?}", crate_name.as_ref()));
+        if output.status.code() != Some(expected) {
+            panic!(
+                "$ {}\nexpected exit code {}, {}\n---------------------------\n{}\n---------------------------\n{}",
+                invocation_line(crate_name.as_ref(), args),
+                expected,
+                describe_status(&output.status),
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    pub fn assert_no_stderr<C: AsRef<str>>(&self, crate_name: C, args: &[&str]) -> CommandOutcome {
+        let outcome = self.run(crate_name.as_ref(), args);
+        if !outcome.stderr.is_empty() {
+            panic!(
+                "$ {}\nexpected no stderr, found {} bytes\n---------------------------\n{}",
+                invocation_line(crate_name.as_ref(), args),
+                outcome.stderr.len(),
+                String::from_utf8_lossy(&outcome.stderr)
+            );
+        }
+        outcome
+    }
+
+    pub fn assert_exit_code(&self, output: &Output, expected: i32) {
+        if output.status.code() != Some(expected) {
+            self.panic_on_status(output, &format!("expected exit code {}", expected));
+        }
+    }
+
+    pub fn assert_success(&self, output: &Output) {
+        if !output.status.success() {
+            self.panic_on_status(output, "expected success");
+        }
+    }
+
+    fn panic_on_status(&self, output: &Output, expected: &str) -> ! {
+        let mut report = format!(
+            "assertion failed on {:?}: {}, {}\n",
+            self.label,
+            expected,
+            describe_status(&output.status)
+        );
+        report.push_str(&self.invocation_report());
+        report.push_str("tree:\n");
+        for path in self.full_tree() {
+            if !path.as_os_str().is_empty() {
+                report.push_str(&format!("  {}\n", display_path(&path)));
+            }
+        }
+        panic!("{}{}", report, format_output(output));
+    }
+
+    // `^` and `$` match at line boundaries, so a single log line can be matched.
+    #[cfg(feature = "regex")]
+    pub fn assert_stderr_matches<C: AsRef<str>>(
+        &self,
+        crate_name: C,
+        args: &[&str],
+        pattern: &str,
+    ) {
+        let regex = regex::RegexBuilder::new(pattern)
+            .multi_line(true)
+            .build()
+            .unwrap_or_else(|error| panic!("invalid pattern {:?}: {}", pattern, error));
+        let output = self
+            .command_args(crate_name.as_ref(), args)
+            .output()
+            .unwrap_or_else(|_| panic!("fail to run {:?}", crate_name.as_ref()));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !regex.is_match(&stderr) {
+            panic!(
+                "$ {}\nstderr does not match {:?}\n---------------------------\n{}",
+                invocation_line(crate_name.as_ref(), args),
+                pattern,
+                stderr
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    pub fn assert_mode<P: AsRef<Path>>(&self, path: P, expected: u32) {
+        use std::os::unix::fs::PermissionsExt;
+        let full_path = self.live_path(path.as_ref(), "read metadata");
+        let metadata = full_path.metadata().unwrap_or_else(|_| {
+            panic!("assertion failed: file {:?} does not exist", path.as_ref())
+        });
+        let mode = metadata.permissions().mode() & 0o777;
+        if mode != expected & 0o777 {
+            panic!(
+                "assertion failed on file {:?}: expected mode {:o}, found {:o}",
+                path.as_ref(),
+                expected,
+                mode
+            );
+        }
+    }
+
+    pub fn assert_tree_eq(&self, expected: &[&str]) {
+        let actual: BTreeSet<String> = self
+            .tree_files()
+            .iter()
+            .map(|path| display_path(path))
+            .collect();
+        let expected: BTreeSet<String> = expected.iter().map(|path| path.to_string()).collect();
+        if actual != expected {
+            let mut report = String::from("assertion failed: tree does not match\n");
+            for path in expected.difference(&actual) {
+                report.push_str(&format!("  - {} (missing)\n", path));
+            }
+            for path in actual.difference(&expected) {
+                report.push_str(&format!("  + {} (unexpected)\n", path));
+            }
+            panic!("{}", report);
+        }
+    }
+
+    pub fn assert_files_eq<P: AsRef<Path>, Q: AsRef<Path>>(&self, a: P, b: Q) {
+        let left = self.read_asserted_file(a.as_ref());
+        let right = self.read_asserted_file(b.as_ref());
+        if left == right {
+            return;
+        }
+        // When one file is a prefix of the other, they differ at the end of the shortest.
+        let offset = left
+            .iter()
+            .zip(right.iter())
+            .position(|(left, right)| left != right)
+            .unwrap_or_else(|| left.len().min(right.len()));
+        panic!(
+            "assertion failed: files {:?} ({} bytes) and {:?} ({} bytes) differ at offset {}",
+            a.as_ref(),
+            left.len(),
+            b.as_ref(),
+            right.len(),
+            offset
+        );
+    }
+
+    fn read_asserted_file(&self, path: &Path) -> Vec<u8> {
+        let full_path = self.live_path(path, "read file");
+        if !full_path.is_file() {
+            panic!("assertion failed: file {:?} does not exist", path);
+        }
+        read(&full_path).unwrap_or_else(|_| panic!("fail to read file {:?}", full_path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use predicates::prelude::*;
+    use predicates::str::contains;
+
+    fn environment() -> IntegrationTestEnvironment {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("file1", "hello world");
+        e.setup();
+        e
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn assert_mode() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("id_rsa", "secret");
+        e.setup();
+        e.set_permissions_recursive("id_rsa", 0o600).unwrap();
+        e.assert_mode("id_rsa", 0o600);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    #[should_panic(expected = "assertion failed on file \"id_rsa\": expected mode 600, found 644")]
+    fn assert_mode_mismatch() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("id_rsa", "secret");
+        e.setup();
+        e.set_permissions_recursive("id_rsa", 0o644).unwrap();
+        e.assert_mode("id_rsa", 0o600);
+    }
+
+    #[test]
+    fn assert_file() {
+        let e = environment();
+        e.assert_file("file1", contains("hello"));
+        e.assert_file_bytes("file1", predicate::eq(&b"hello world"[..]));
+    }
+
+    #[test]
+    fn assert_files_eq() {
+        let mut e = environment();
+        e.add_file("file2", "hello world");
+        e.setup();
+        e.assert_files_eq("file1", "file2");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: files \"file1\" (11 bytes) and \"file2\" (11 bytes) differ at offset 6"
+    )]
+    fn assert_files_eq_mismatch() {
+        let mut e = environment();
+        e.add_file("file2", "hello there");
+        e.setup();
+        e.assert_files_eq("file1", "file2");
+    }
+
+    #[test]
+    #[should_panic(expected = "(11 bytes) and \"file2\" (5 bytes) differ at offset 5")]
+    fn assert_files_eq_prefix() {
+        let mut e = environment();
+        e.add_file("file2", "hello");
+        e.setup();
+        e.assert_files_eq("file1", "file2");
+    }
+
+    #[test]
+    fn assert_tree_eq() {
+        let mut e = environment();
+        e.add_file("dir/file2", "");
+        e.setup();
+        e.assert_tree_eq(&["dir/file2", "file1"]);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "assertion failed: tree does not match\n  - dir/missing (missing)\n  + file1 (unexpected)\n"
+    )]
+    fn assert_tree_eq_mismatch() {
+        let e = environment();
+        e.assert_tree_eq(&["dir/missing"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed on file \"file1\"")]
+    fn assert_file_mismatch() {
+        let e = environment();
+        e.assert_file("file1", contains("goodbye"));
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed: file \"missing\" does not exist")]
+    fn assert_file_missing() {
+        let e = environment();
+        e.assert_file("missing", contains("hello"));
+    }
+}
@@ -0,0 +1,91 @@
+use crate::IntegrationTestEnvironment;
+use std::cell::RefCell;
+use std::io;
+use std::process::{Child, ExitStatus};
+use std::time::Duration;
+
+// The child is killed when the handle is dropped, so a failing test does not leave a
+// daemon running.
+pub struct BackgroundProcess {
+    child: RefCell<Child>,
+}
+
+impl BackgroundProcess {
+    pub fn id(&self) -> u32 {
+        self.child.borrow().id()
+    }
+
+    #[cfg(unix)]
+    pub fn signal(&self, sig: i32) -> io::Result<()> {
+        let result = unsafe { libc::kill(self.id() as libc::pid_t, sig) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn signal(&self, _sig: i32) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "signals are only supported on unix",
+        ))
+    }
+
+    // SIGTERM, then SIGKILL if the process is still running after `grace`.
+    #[cfg(unix)]
+    pub fn terminate_gracefully(&self, grace: Duration) -> io::Result<ExitStatus> {
+        use std::thread::sleep;
+        use std::time::Instant;
+        const POLL_INTERVAL: Duration = Duration::from_millis(10);
+        self.signal(libc::SIGTERM)?;
+        let start = Instant::now();
+        loop {
+            if let Some(status) = self.child.borrow_mut().try_wait()? {
+                return Ok(status);
+            }
+            if start.elapsed() >= grace {
+                let mut child = self.child.borrow_mut();
+                child.kill()?;
+                return child.wait();
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn terminate_gracefully(&self, _grace: Duration) -> io::Result<ExitStatus> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "graceful termination is only supported on unix",
+        ))
+    }
+
+    pub fn wait(&self) -> io::Result<ExitStatus> {
+        self.child.borrow_mut().wait()
+    }
+}
+
+impl Drop for BackgroundProcess {
+    fn drop(&mut self) {
+        let child = self.child.get_mut();
+        if let Ok(None) = child.try_wait() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn spawn_background<C: AsRef<str>>(
+        &self,
+        crate_name: C,
+        args: &[&str],
+    ) -> io::Result<BackgroundProcess> {
+        let child = self.spawn(crate_name, args)?;
+        Ok(BackgroundProcess {
+            child: RefCell::new(child),
+        })
+    }
+}
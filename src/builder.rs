@@ -0,0 +1,222 @@
+use crate::IntegrationTestEnvironment;
+use assert_cmd::Command;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Clone)]
+enum Staged {
+    File(PathBuf, String),
+    Dir(PathBuf),
+}
+
+type SharedCallback = Rc<dyn Fn(&str, &Path, Command) -> Command>;
+
+// Holds the configuration until `build()`, so that a base builder can be cloned
+// and extended per test before any temporary directory exists.
+#[derive(Clone)]
+pub struct IntegrationTestEnvironmentBuilder {
+    label: String,
+    staged: Vec<Staged>,
+    envs: Vec<(String, String)>,
+    callback: Option<SharedCallback>,
+}
+
+impl Default for IntegrationTestEnvironmentBuilder {
+    fn default() -> Self {
+        Self {
+            label: "integration_test".to_string(),
+            staged: vec![],
+            envs: vec![],
+            callback: None,
+        }
+    }
+}
+
+impl IntegrationTestEnvironmentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn label<L: AsRef<str>>(mut self, label: L) -> Self {
+        self.label = label.as_ref().to_string();
+        self
+    }
+
+    pub fn file<P: AsRef<Path>, C: AsRef<str>>(mut self, path: P, content: C) -> Self {
+        self.staged.push(Staged::File(
+            path.as_ref().to_path_buf(),
+            content.as_ref().to_string(),
+        ));
+        self
+    }
+
+    pub fn dir<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.staged.push(Staged::Dir(path.as_ref().to_path_buf()));
+        self
+    }
+
+    pub fn env<K: AsRef<str>, V: AsRef<str>>(mut self, key: K, value: V) -> Self {
+        self.envs
+            .push((key.as_ref().to_string(), value.as_ref().to_string()));
+        self
+    }
+
+    pub fn callback(
+        mut self,
+        callback: impl Fn(&str, &Path, Command) -> Command + 'static,
+    ) -> Self {
+        self.callback = Some(Rc::new(callback));
+        self
+    }
+
+    pub fn build(&self) -> IntegrationTestEnvironment {
+        let mut environment = IntegrationTestEnvironment::new(&self.label);
+        for staged in &self.staged {
+            match staged {
+                Staged::File(path, content) => environment.add_file(path, content),
+                Staged::Dir(path) => environment.add_dir(path),
+            }
+        }
+        for (key, value) in &self.envs {
+            environment.set_env(key, value);
+        }
+        if let Some(callback) = &self.callback {
+            let callback = Rc::clone(callback);
+            environment.set_command_callback(move |crate_name, path, command| {
+                callback(crate_name, path, command)
+            });
+        }
+        environment
+    }
+
+    pub fn build_and_setup(&self) -> IntegrationTestEnvironment {
+        let environment = self.build();
+        environment.setup();
+        environment
+    }
+}
+
+// Returned by `IntegrationTestEnvironment::builder`, the environment is set up
+// by `build()` so it can not be used before its entries exist.
+#[derive(Clone)]
+pub struct ReadyEnvBuilder {
+    builder: IntegrationTestEnvironmentBuilder,
+}
+
+impl ReadyEnvBuilder {
+    pub fn file<P: AsRef<Path>, C: AsRef<str>>(self, path: P, content: C) -> Self {
+        Self {
+            builder: self.builder.file(path, content),
+        }
+    }
+
+    pub fn dir<P: AsRef<Path>>(self, path: P) -> Self {
+        Self {
+            builder: self.builder.dir(path),
+        }
+    }
+
+    pub fn env<K: AsRef<str>, V: AsRef<str>>(self, key: K, value: V) -> Self {
+        Self {
+            builder: self.builder.env(key, value),
+        }
+    }
+
+    pub fn callback(self, callback: impl Fn(&str, &Path, Command) -> Command + 'static) -> Self {
+        Self {
+            builder: self.builder.callback(callback),
+        }
+    }
+
+    pub fn build(&self) -> ReadyEnv {
+        ReadyEnv {
+            environment: self.builder.build_and_setup(),
+        }
+    }
+}
+
+pub struct ReadyEnv {
+    environment: IntegrationTestEnvironment,
+}
+
+impl ReadyEnv {
+    pub fn into_inner(self) -> IntegrationTestEnvironment {
+        self.environment
+    }
+}
+
+impl Deref for ReadyEnv {
+    type Target = IntegrationTestEnvironment;
+
+    fn deref(&self) -> &IntegrationTestEnvironment {
+        &self.environment
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn builder<L: AsRef<str>>(label: L) -> ReadyEnvBuilder {
+        ReadyEnvBuilder {
+            builder: IntegrationTestEnvironmentBuilder::new().label(label),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{IntegrationTestEnvironment, IntegrationTestEnvironmentBuilder};
+    use std::path::PathBuf;
+
+    #[test]
+    fn builder() {
+        let base = IntegrationTestEnvironmentBuilder::new()
+            .label("base")
+            .file("config.toml", "key = 1")
+            .dir("data")
+            .env("MODE", "test")
+            .callback(|_, _, command| command);
+
+        let first = base
+            .clone()
+            .file("config.toml", "key = 2")
+            .build_and_setup();
+        let second = base.clone().file("extra", "second").build_and_setup();
+
+        assert_eq!(first.read_file("config.toml"), "key = 2");
+        assert!(!first.file_exists("extra"));
+        assert_eq!(second.read_file("config.toml"), "key = 1");
+        assert_eq!(second.read_file("extra"), "second");
+        assert_ne!(first.path().unwrap(), second.path().unwrap());
+        assert_eq!(
+            first.env_vars().get("MODE").map(String::as_str),
+            Some("test")
+        );
+
+        let planned = base.build();
+        assert_eq!(
+            planned.planned_entries(),
+            vec![
+                (PathBuf::from("config.toml"), false),
+                (PathBuf::from("data"), true)
+            ]
+        );
+        assert_eq!(planned.tree(), vec![PathBuf::from("")]);
+    }
+
+    #[test]
+    fn ready_env() {
+        let e = IntegrationTestEnvironment::builder("ready")
+            .file("config.toml", "key = 1")
+            .dir("data")
+            .build();
+        assert_eq!(e.read_file("config.toml"), "key = 1");
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("config.toml"),
+                PathBuf::from("data")
+            ]
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use crate::{display_path, permissions_mode, IntegrationTestEnvironment};
+use owo_colors::{OwoColorize, Stream};
+
+impl IntegrationTestEnvironment {
+    pub fn tree_colored(&self) -> String {
+        let mut tree = String::new();
+        for relative in self.full_tree() {
+            let path = self.tmp_dir.path().join(&relative);
+            let name = display_path(&relative);
+            let metadata = path.symlink_metadata().ok();
+            let is_dir = metadata.as_ref().is_some_and(|metadata| metadata.is_dir());
+            let is_executable = metadata.as_ref().is_some_and(|metadata| {
+                metadata.is_file() && cfg!(unix) && permissions_mode(metadata) & 0o111 != 0
+            });
+            let name = if is_dir {
+                name.if_supports_color(Stream::Stdout, |name| name.blue())
+                    .to_string()
+            } else if is_executable {
+                name.if_supports_color(Stream::Stdout, |name| name.green())
+                    .to_string()
+            } else {
+                name
+            };
+            tree.push_str(&name);
+            tree.push('\n');
+        }
+        tree
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+
+    #[test]
+    fn tree_colored() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("dir/file1", "test 1");
+        e.add_file("run.sh", "echo test");
+        e.setup();
+        e.make_executable("run.sh").unwrap();
+
+        owo_colors::set_override(true);
+        let tree = e.tree_colored();
+        owo_colors::unset_override();
+
+        assert!(tree.contains("\u{1b}[34mdir\u{1b}[39m\n"));
+        assert!(tree.contains("dir/file1\n"));
+        #[cfg(unix)]
+        assert!(tree.contains("\u{1b}[32mrun.sh\u{1b}[39m\n"));
+    }
+}
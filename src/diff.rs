@@ -0,0 +1,224 @@
+use crate::{display_path, permissions_mode, IntegrationTestEnvironment};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::{read, read_link, symlink_metadata};
+use std::path::{Path, PathBuf};
+
+const TEXT_DIFF_MAX_SIZE: usize = 64 * 1024;
+
+pub(crate) fn unified_diff(expected: &str, actual: &str, from: &str, to: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    difflib::unified_diff(&expected, &actual, from, to, "", "", 3)
+        .iter()
+        .map(|line| format!("{}\n", line.trim_end_matches(&['\t', '\n'][..])))
+        .collect()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnvDiff {
+    pub only_in_self: Vec<PathBuf>,
+    pub only_in_other: Vec<PathBuf>,
+    pub differing: Vec<PathBuf>,
+    details: Vec<String>,
+}
+
+impl EnvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.only_in_self.is_empty() && self.only_in_other.is_empty() && self.differing.is_empty()
+    }
+}
+
+impl Display for EnvDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+        for (title, paths) in [
+            ("only in self", &self.only_in_self),
+            ("only in other", &self.only_in_other),
+        ] {
+            if paths.is_empty() {
+                continue;
+            }
+            writeln!(f, "{} ({}):", title, paths.len())?;
+            for path in paths {
+                writeln!(f, "  {}", display_path(path))?;
+            }
+        }
+        if !self.differing.is_empty() {
+            writeln!(f, "differing ({}):", self.differing.len())?;
+            for (path, detail) in self.differing.iter().zip(self.details.iter()) {
+                writeln!(f, "  {}: {}", display_path(path), detail.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Node {
+    Dir,
+    File(Vec<u8>),
+    Symlink(PathBuf),
+    // Named pipes and other special files, reading them could block.
+    Special,
+}
+
+fn read_node(path: &Path) -> (Node, u32) {
+    let metadata =
+        symlink_metadata(path).unwrap_or_else(|_| panic!("fail to read metadata {:?}", path));
+    let node = if metadata.file_type().is_symlink() {
+        Node::Symlink(read_link(path).unwrap_or_else(|_| panic!("fail to read link {:?}", path)))
+    } else if metadata.is_dir() {
+        Node::Dir
+    } else if !metadata.is_file() {
+        Node::Special
+    } else {
+        Node::File(read(path).unwrap_or_else(|_| panic!("fail to read file {:?}", path)))
+    };
+    (node, permissions_mode(&metadata))
+}
+
+fn describe_difference(path: &Path, left: &(Node, u32), right: &(Node, u32)) -> Option<String> {
+    let mut details = vec![];
+    match (&left.0, &right.0) {
+        (Node::Dir, Node::Dir) => {}
+        (Node::File(left), Node::File(right)) if left != right => {
+            match (std::str::from_utf8(left), std::str::from_utf8(right)) {
+                (Ok(left), Ok(right))
+                    if left.len() <= TEXT_DIFF_MAX_SIZE && right.len() <= TEXT_DIFF_MAX_SIZE =>
+                {
+                    let name = display_path(path);
+                    details.push(format!(
+                        "content differs\n{}",
+                        unified_diff(
+                            left,
+                            right,
+                            &format!("self/{}", name),
+                            &format!("other/{}", name)
+                        )
+                    ));
+                }
+                _ => details.push(format!(
+                    "content differs ({} bytes != {} bytes)",
+                    left.len(),
+                    right.len()
+                )),
+            }
+        }
+        (Node::File(_), Node::File(_)) => {}
+        (Node::Symlink(left), Node::Symlink(right)) if left != right => {
+            details.push(format!("symlink target {:?} != {:?}", left, right))
+        }
+        (Node::Symlink(_), Node::Symlink(_)) => {}
+        (Node::Special, Node::Special) => {}
+        (left, right) => details.push(format!("type {} != {}", node_type(left), node_type(right))),
+    }
+    if left.1 != right.1 {
+        details.insert(0, format!("mode {:o} != {:o}", left.1, right.1));
+    }
+    if details.is_empty() {
+        None
+    } else {
+        Some(details.join(", "))
+    }
+}
+
+fn node_type(node: &Node) -> &'static str {
+    match node {
+        Node::Dir => "directory",
+        Node::File(_) => "file",
+        Node::Symlink(_) => "symlink",
+        Node::Special => "special file",
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn diff_with(&self, other: &IntegrationTestEnvironment) -> EnvDiff {
+        let mut paths: BTreeMap<PathBuf, (bool, bool)> = BTreeMap::new();
+        for path in self.full_tree() {
+            paths.entry(path).or_default().0 = true;
+        }
+        for path in other.full_tree() {
+            paths.entry(path).or_default().1 = true;
+        }
+        let mut diff = EnvDiff::default();
+        for (path, presence) in paths {
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            match presence {
+                (true, false) => diff.only_in_self.push(path),
+                (false, true) => diff.only_in_other.push(path),
+                _ => {
+                    let left = read_node(&self.tmp_dir.path().join(&path));
+                    let right = read_node(&other.tmp_dir.path().join(&path));
+                    if let Some(detail) = describe_difference(&path, &left, &right) {
+                        diff.differing.push(path);
+                        diff.details.push(detail);
+                    }
+                }
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::diff::unified_diff;
+
+    #[test]
+    fn unified_diff_format() {
+        let diff = unified_diff("a\nb\nc\n", "a\nB\nc\n", "expected", "actual");
+        assert_eq!(
+            diff,
+            "--- expected\n+++ actual\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+        );
+        assert_eq!(unified_diff("same\n", "same\n", "expected", "actual"), "");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn diff_with() {
+        use crate::IntegrationTestEnvironment;
+        use std::os::unix::fs::symlink;
+        use std::path::PathBuf;
+
+        let mut a = IntegrationTestEnvironment::new("a");
+        a.add_file("same", "same");
+        a.add_file("content", "line 1\nline 2\n");
+        a.add_file("mode", "mode");
+        a.add_file("only_in_a", "a");
+        a.setup();
+        a.set_exec_permission("mode").unwrap();
+        symlink("same", a.path().unwrap().join("link")).unwrap();
+
+        let mut b = IntegrationTestEnvironment::new("b");
+        b.add_file("same", "same");
+        b.add_file("content", "line 1\nline two\n");
+        b.add_file("mode", "mode");
+        b.add_dir("empty_dir");
+        b.setup();
+        symlink("content", b.path().unwrap().join("link")).unwrap();
+
+        let diff = a.diff_with(&b);
+        assert!(!diff.is_empty());
+        assert_eq!(diff.only_in_self, vec![PathBuf::from("only_in_a")]);
+        assert_eq!(diff.only_in_other, vec![PathBuf::from("empty_dir")]);
+        assert_eq!(
+            diff.differing,
+            vec![
+                PathBuf::from("content"),
+                PathBuf::from("link"),
+                PathBuf::from("mode")
+            ]
+        );
+        let display = diff.to_string();
+        assert!(display.contains("-line 2\n+line two\n"));
+        assert!(display.contains("link: symlink target \"same\" != \"content\""));
+        assert!(display.contains("mode: mode 755 != "));
+        assert!(a.diff_with(&a).is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::fixture::CopyMode;
+use std::cell::{Ref, RefCell};
+use std::fmt::{Debug, Formatter};
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[derive(Clone)]
+pub(crate) enum Entry {
+    File(String),
+    Bytes(Vec<u8>),
+    Generated(Rc<Generator>),
+    Hardlink(PathBuf),
+    Copy {
+        source: PathBuf,
+        mode: CopyMode,
+    },
+    #[cfg_attr(
+        not(any(all(unix, feature = "fifo"), feature = "serde")),
+        allow(dead_code)
+    )]
+    Fifo,
+    #[cfg(feature = "sqlite")]
+    Sqlite(String),
+    Dir,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EntryKind {
+    File,
+    Dir,
+    Hardlink,
+    Fifo,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwritePolicy {
+    LastWins,
+    FirstWins,
+    Panic,
+}
+
+impl Entry {
+    pub(crate) fn describe(&self) -> String {
+        match self {
+            Entry::File(content) => format!("file {}", preview(content, content.len())),
+            Entry::Bytes(_) => format!("binary file {}", self.lossy_preview()),
+            Entry::Generated(_) => "generated file".to_string(),
+            Entry::Hardlink(target) => format!("hard link to {:?}", target),
+            Entry::Copy { source, .. } => format!("copy of {:?}", source),
+            Entry::Fifo => "named pipe".to_string(),
+            #[cfg(feature = "sqlite")]
+            Entry::Sqlite(_) => "sqlite database".to_string(),
+            Entry::Dir => "directory".to_string(),
+        }
+    }
+
+    // Binary fixtures are often mostly text, a lossy preview keeps them recognizable.
+    pub(crate) fn lossy_preview(&self) -> String {
+        match self {
+            Entry::Bytes(content) => preview(&String::from_utf8_lossy(content), content.len()),
+            _ => String::new(),
+        }
+    }
+
+    pub(crate) fn is_dir(&self) -> bool {
+        matches!(self, Entry::Dir)
+    }
+
+    pub(crate) fn kind(&self) -> EntryKind {
+        match self {
+            Entry::File(_) | Entry::Bytes(_) | Entry::Generated(_) | Entry::Copy { .. } => {
+                EntryKind::File
+            }
+            #[cfg(feature = "sqlite")]
+            Entry::Sqlite(_) => EntryKind::File,
+            Entry::Hardlink(_) => EntryKind::Hardlink,
+            Entry::Fifo => EntryKind::Fifo,
+            Entry::Dir => EntryKind::Dir,
+        }
+    }
+}
+
+// Shared by `Debug` and the panic messages, so that both truncate the same way.
+fn preview(content: &str, bytes: usize) -> String {
+    const MAX_CHARS: usize = 80;
+    if content.chars().count() <= MAX_CHARS {
+        return format!("{:?}", content);
+    }
+    let truncated: String = content.chars().take(MAX_CHARS).collect();
+    format!("{:?}\u{2026} ({} bytes)", truncated, bytes)
+}
+
+// Shown by `Debug` on the environment, generators are not run and sources are not read.
+pub(crate) struct ContentPreview<'a>(pub(crate) &'a Entry);
+
+impl Debug for ContentPreview<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let preview = match self.0 {
+            Entry::File(content) => preview(content, content.len()),
+            Entry::Bytes(_) => self.0.lossy_preview(),
+            Entry::Generated(_) => return write!(f, "<generated>"),
+            Entry::Hardlink(target) => return write!(f, "<hard link to {:?}>", target),
+            Entry::Copy { source, .. } => return write!(f, "<copy of {:?}>", source),
+            Entry::Fifo => return write!(f, "<fifo>"),
+            #[cfg(feature = "sqlite")]
+            Entry::Sqlite(_) => return write!(f, "<sqlite database>"),
+            Entry::Dir => return write!(f, "<dir>"),
+        };
+        write!(f, "{}", preview)
+    }
+}
+
+enum GeneratorState {
+    Pending(Box<dyn FnOnce() -> String>),
+    Running,
+    Done(String),
+}
+
+// Runs the generator the first time the content is needed and keeps the result,
+// so the closure is invoked at most once even if `setup()` is called again.
+pub(crate) struct Generator {
+    state: RefCell<GeneratorState>,
+}
+
+impl Generator {
+    pub(crate) fn new(generator: impl FnOnce() -> String + 'static) -> Self {
+        Self {
+            state: RefCell::new(GeneratorState::Pending(Box::new(generator))),
+        }
+    }
+
+    pub(crate) fn content(&self) -> Ref<'_, String> {
+        let state = self.state.replace(GeneratorState::Running);
+        let state = match state {
+            GeneratorState::Pending(generator) => GeneratorState::Done(generator()),
+            state => state,
+        };
+        self.state.replace(state);
+        Ref::map(self.state.borrow(), |state| match state {
+            GeneratorState::Done(content) => content,
+            _ => panic!("file generator has been called recursively"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Entry;
+
+    #[test]
+    fn describe_bytes_preview() {
+        let entry = Entry::Bytes(b"key = 1\0\xff".to_vec());
+        assert_eq!(entry.describe(), "binary file \"key = 1\\0\u{fffd}\"");
+        let long = Entry::Bytes(vec![b'a'; 100]);
+        assert_eq!(
+            long.describe(),
+            format!("binary file \"{}\"\u{2026} (100 bytes)", "a".repeat(80))
+        );
+        assert_eq!(
+            format!("{:?}", super::ContentPreview(&long)),
+            long.describe().trim_start_matches("binary file ")
+        );
+    }
+}
@@ -0,0 +1,41 @@
+use assert_cmd::cargo::CargoError;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+#[derive(Debug)]
+pub enum CargoBinError {
+    NotFound {
+        crate_name: String,
+        cause: CargoError,
+    },
+    Io(io::Error),
+}
+
+impl Display for CargoBinError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CargoBinError::NotFound { crate_name, cause } => write!(
+                f,
+                "binary {:?} not found, it may not be a [[bin]] target of this package or it has not been built yet\n{}",
+                crate_name, cause
+            ),
+            CargoBinError::Io(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl Error for CargoBinError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CargoBinError::NotFound { cause, .. } => Some(cause),
+            CargoBinError::Io(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for CargoBinError {
+    fn from(error: io::Error) -> Self {
+        CargoBinError::Io(error)
+    }
+}
@@ -0,0 +1,131 @@
+use crate::fifo::{create_fifo, is_fifo};
+use crate::IntegrationTestEnvironment;
+use std::fs::{copy, create_dir_all, read_dir, read_link, remove_file, set_permissions};
+use std::io;
+use std::path::Path;
+use walkdir::WalkDir;
+
+impl IntegrationTestEnvironment {
+    // Exporting into a non-empty directory is refused unless `overwrite` is set, then
+    // the exported entries replace the existing ones and the others are kept.
+    pub fn export_to<P: AsRef<Path>>(&self, dest: P, overwrite: bool) -> io::Result<()> {
+        let dest = dest.as_ref();
+        create_dir_all(dest)?;
+        if !overwrite && read_dir(dest)?.next().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{:?} is not empty", dest),
+            ));
+        }
+        let root = self.tmp_dir.path();
+        let mut dirs = vec![];
+        for dir_entry in WalkDir::new(root).min_depth(1) {
+            let dir_entry = dir_entry?;
+            let path = dest.join(
+                dir_entry
+                    .path()
+                    .strip_prefix(root)
+                    .expect("entry outside of the environment"),
+            );
+            let file_type = dir_entry.file_type();
+            if file_type.is_dir() {
+                create_dir_all(&path)?;
+                dirs.push((path, dir_entry.metadata()?.permissions()));
+                continue;
+            }
+            match remove_file(&path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+                _ => {}
+            }
+            if file_type.is_symlink() {
+                symlink(&read_link(dir_entry.path())?, &path)?;
+            } else if file_type.is_file() {
+                copy(dir_entry.path(), &path)?;
+            } else if is_fifo(&file_type) {
+                create_fifo(&path)?;
+                set_permissions(&path, dir_entry.metadata()?.permissions())?;
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!(
+                        "{:?} is not a file, a directory or a link",
+                        dir_entry.path()
+                    ),
+                ));
+            }
+        }
+        // Read-only directories would prevent creating their content.
+        for (path, permissions) in dirs.into_iter().rev() {
+            set_permissions(path, permissions)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> io::Result<()> {
+    let resolved = path.parent().unwrap_or(path).join(target);
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, path)
+    } else {
+        std::os::windows::fs::symlink_file(target, path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::fs::write;
+    use std::io;
+    use tempdir::TempDir;
+
+    fn environment() -> IntegrationTestEnvironment {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("config.toml", "key = 1");
+        e.add_executable_file("bin/run.sh", "echo run");
+        e.add_dir("data/empty");
+        e.setup();
+        e
+    }
+
+    #[test]
+    fn export_to() {
+        let e = environment();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("config.toml", e.path().unwrap().join("link")).unwrap();
+        let exported = IntegrationTestEnvironment::new("exported");
+        e.export_to(exported.path().unwrap(), false).unwrap();
+        let diff = e.diff_with(&exported);
+        assert!(diff.is_empty(), "{}", diff);
+        #[cfg(unix)]
+        assert!(exported
+            .path()
+            .unwrap()
+            .join("link")
+            .symlink_metadata()
+            .unwrap()
+            .file_type()
+            .is_symlink());
+    }
+
+    #[test]
+    fn export_to_non_empty() {
+        let e = environment();
+        let dest = TempDir::new("export").unwrap();
+        write(dest.path().join("config.toml"), "key = 0").unwrap();
+        let error = e.export_to(dest.path(), false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::AlreadyExists);
+
+        e.export_to(dest.path(), true).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dest.path().join("config.toml")).unwrap(),
+            "key = 1"
+        );
+        assert!(dest.path().join("data/empty").is_dir());
+    }
+}
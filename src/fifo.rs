@@ -0,0 +1,79 @@
+use crate::IntegrationTestEnvironment;
+use std::fs::FileType;
+use std::io;
+use std::path::Path;
+
+impl IntegrationTestEnvironment {
+    // The pipe is created by `setup()`, opening it blocks until the other end is opened.
+    #[cfg(all(unix, feature = "fifo"))]
+    pub fn add_fifo<P: AsRef<Path>>(&mut self, path: P) {
+        self.stage(path.as_ref().to_path_buf(), crate::entry::Entry::Fifo);
+    }
+}
+
+// A previous `setup()` may have created it already.
+#[cfg(all(unix, feature = "fifo"))]
+pub(crate) fn create_fifo(path: &Path) -> io::Result<()> {
+    use nix::sys::stat::Mode;
+    use std::fs::remove_file;
+    match remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        _ => {}
+    }
+    nix::unistd::mkfifo(path, Mode::from_bits_truncate(0o644)).map_err(io::Error::from)
+}
+
+#[cfg(not(all(unix, feature = "fifo")))]
+pub(crate) fn create_fifo(path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{:?}: named pipes are only supported on unix, with the fifo feature",
+            path
+        ),
+    ))
+}
+
+#[cfg(unix)]
+pub(crate) fn is_fifo(file_type: &FileType) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    file_type.is_fifo()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn is_fifo(_file_type: &FileType) -> bool {
+    false
+}
+
+#[cfg(all(test, unix, feature = "fifo"))]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::fs::{read_to_string, OpenOptions};
+    use std::io::Write;
+    use std::os::unix::fs::FileTypeExt;
+    use std::thread::spawn;
+
+    #[test]
+    fn add_fifo() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_fifo("ipc/requests");
+        assert_eq!(
+            e.dry_run(),
+            vec!["create dir ipc", "create fifo ipc/requests"]
+        );
+        e.setup();
+        e.setup();
+        let path = e.path().unwrap().join("ipc/requests");
+        assert!(path.metadata().unwrap().file_type().is_fifo());
+        e.capture();
+        e.hash_tree();
+
+        let writer_path = path.clone();
+        let writer = spawn(move || {
+            let mut fifo = OpenOptions::new().write(true).open(writer_path).unwrap();
+            fifo.write_all(b"ping\n").unwrap();
+        });
+        assert_eq!(read_to_string(&path).unwrap(), "ping\n");
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,386 @@
+use crate::entry::Entry;
+use crate::{permissions_mode, IntegrationTestEnvironment};
+use std::fs::{copy, hard_link, read, read_dir, remove_file, rename};
+use std::io;
+use std::path::Path;
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMode {
+    Copy,
+    // Linked files share their content with the fixture: the environment turns them into
+    // private copies before modifying them, the CLI under test must only read them, files
+    // it writes to are staged with `CopyMode::Copy`.
+    Hardlink,
+    #[cfg(feature = "reflink")]
+    Reflink,
+}
+
+impl CopyMode {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            CopyMode::Copy => "copy",
+            CopyMode::Hardlink => "hardlink",
+            #[cfg(feature = "reflink")]
+            CopyMode::Reflink => "reflink",
+        }
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn copy_fixture<S, D>(&mut self, source: S, dest: D, mode: CopyMode) -> io::Result<()>
+    where
+        S: AsRef<Path>,
+        D: AsRef<Path>,
+    {
+        self.copy_fixture_filtered(source, dest, mode, |_| true)
+    }
+
+    // The filter receives paths relative to the fixture root, a rejected directory is
+    // skipped with everything it contains.
+    pub fn copy_fixture_filtered<S, D, F>(
+        &mut self,
+        source: S,
+        dest: D,
+        mode: CopyMode,
+        filter: F,
+    ) -> io::Result<()>
+    where
+        S: AsRef<Path>,
+        D: AsRef<Path>,
+        F: Fn(&Path) -> bool,
+    {
+        let source = source.as_ref().canonicalize()?;
+        let dest = dest.as_ref();
+        let walker = WalkDir::new(&source).into_iter().filter_entry(|dir_entry| {
+            match dir_entry.path().strip_prefix(&source) {
+                Ok(relative) => relative.as_os_str().is_empty() || filter(relative),
+                Err(_) => false,
+            }
+        });
+        for dir_entry in walker {
+            let dir_entry = dir_entry?;
+            let path = dest.join(
+                dir_entry
+                    .path()
+                    .strip_prefix(&source)
+                    .expect("fixture entry outside of the fixture"),
+            );
+            let file_type = dir_entry.file_type();
+            if file_type.is_dir() {
+                if !path.as_os_str().is_empty() {
+                    self.stage(path, Entry::Dir);
+                }
+            } else if file_type.is_file() {
+                self.stage(
+                    path,
+                    Entry::Copy {
+                        source: dir_entry.path().to_path_buf(),
+                        mode,
+                    },
+                );
+            } else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "fixture entry {:?} is not a file or a directory",
+                        dir_entry.path()
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Contents are read right away, so entries can be overridden before `setup()`. Only
+    // empty directories are registered, the others are created with their files.
+    pub fn from_dir<L, P>(label: L, source: P) -> Self
+    where
+        L: AsRef<str>,
+        P: AsRef<Path>,
+    {
+        let source = source.as_ref();
+        let mut environment = IntegrationTestEnvironment::new(label);
+        for dir_entry in WalkDir::new(source).min_depth(1) {
+            let dir_entry =
+                dir_entry.unwrap_or_else(|error| panic!("fail to read {:?}: {}", source, error));
+            let path = dir_entry
+                .path()
+                .strip_prefix(source)
+                .expect("fixture entry outside of the fixture")
+                .to_path_buf();
+            let file_type = dir_entry.file_type();
+            if file_type.is_dir() {
+                let is_empty = read_dir(dir_entry.path())
+                    .map(|mut children| children.next().is_none())
+                    .unwrap_or_else(|error| {
+                        panic!("fail to read {:?}: {}", dir_entry.path(), error)
+                    });
+                if is_empty {
+                    environment.stage(path, Entry::Dir);
+                }
+            } else if file_type.is_file() {
+                let content = read(dir_entry.path()).unwrap_or_else(|error| {
+                    panic!("fail to read {:?}: {}", dir_entry.path(), error)
+                });
+                let entry = match String::from_utf8(content) {
+                    Ok(content) => Entry::File(content),
+                    Err(error) => Entry::Bytes(error.into_bytes()),
+                };
+                environment.stage(path.clone(), entry);
+                let metadata = dir_entry.metadata().unwrap_or_else(|error| {
+                    panic!("fail to read metadata {:?}: {}", dir_entry.path(), error)
+                });
+                let mode = permissions_mode(&metadata);
+                if cfg!(unix) && mode & 0o111 != 0 {
+                    environment.modes.insert(path, mode);
+                }
+            } else {
+                panic!(
+                    "fail to add {:?}: fixture entry is not a file or a directory",
+                    dir_entry.path()
+                );
+            }
+        }
+        environment
+    }
+
+    // Replaces a hard linked copy by a private one, so that writing to it leaves the
+    // fixture untouched.
+    pub(crate) fn detach<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let relative = path.as_ref();
+        if let Some(Entry::Copy {
+            mode: CopyMode::Hardlink,
+            ..
+        }) = self.entries.get(relative)
+        {
+            let path = self.tmp_dir.path().join(relative);
+            if is_linked(&path)? {
+                let mut detached = path.clone().into_os_string();
+                detached.push(".detached");
+                copy(&path, &detached)?;
+                rename(&detached, &path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn is_linked(path: &Path) -> io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(path.metadata()?.nlink() > 1)
+}
+
+#[cfg(not(unix))]
+fn is_linked(path: &Path) -> io::Result<bool> {
+    path.metadata().map(|_| true)
+}
+
+pub(crate) fn copy_entry(source: &Path, dest: &Path, mode: CopyMode) -> io::Result<()> {
+    // A previous `setup()` may have linked the destination, writing through it would
+    // modify the fixture.
+    match remove_file(dest) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error),
+        _ => {}
+    }
+    match mode {
+        CopyMode::Copy => copy(source, dest).map(|_| ()),
+        CopyMode::Hardlink => link_or_copy(source, dest, |source, dest| hard_link(source, dest)),
+        #[cfg(feature = "reflink")]
+        CopyMode::Reflink => reflink_copy::reflink_or_copy(source, dest).map(|_| ()),
+    }
+}
+
+// Linking fails across devices or on filesystems without hard links, fall back to a copy.
+fn link_or_copy(
+    source: &Path,
+    dest: &Path,
+    link: fn(&Path, &Path) -> io::Result<()>,
+) -> io::Result<()> {
+    link(source, dest).or_else(|_| copy(source, dest).map(|_| ()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::link_or_copy;
+    use crate::{CopyMode, IntegrationTestEnvironment};
+    use std::fs::{create_dir_all, read_to_string, write};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use tempdir::TempDir;
+
+    fn fixture() -> TempDir {
+        let dir = TempDir::new("fixture").unwrap();
+        create_dir_all(dir.path().join("data/nested")).unwrap();
+        write(dir.path().join("data/large.bin"), "fixture content").unwrap();
+        write(dir.path().join("data/nested/small.txt"), "small").unwrap();
+        dir
+    }
+
+    #[test]
+    fn copy_fixture() {
+        let source = fixture();
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.copy_fixture(source.path().join("data"), "in", CopyMode::Copy)
+            .unwrap();
+        assert_eq!(
+            e.planned_entries(),
+            vec![
+                (PathBuf::from("in"), true),
+                (PathBuf::from("in/large.bin"), false),
+                (PathBuf::from("in/nested"), true),
+                (PathBuf::from("in/nested/small.txt"), false),
+            ]
+        );
+        assert!(e.dry_run()[2].starts_with("copy file in/large.bin from "));
+        assert!(e.dry_run()[2].ends_with("large.bin (copy)"));
+        e.setup();
+        assert_eq!(e.read_file("in/large.bin"), "fixture content");
+        assert_eq!(e.read_file("in/nested/small.txt"), "small");
+    }
+
+    #[test]
+    fn copy_fixture_filtered() {
+        let source = fixture();
+        write(source.path().join("data/nested/skipped.log"), "log").unwrap();
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.copy_fixture_filtered(source.path().join("data"), "in", CopyMode::Copy, |path| {
+            path != Path::new("nested")
+        })
+        .unwrap();
+        e.setup();
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("in"),
+                PathBuf::from("in/large.bin")
+            ]
+        );
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.copy_fixture_filtered(source.path().join("data"), "", CopyMode::Copy, |path| {
+            path.extension().is_none_or(|extension| extension != "log")
+        })
+        .unwrap();
+        e.setup();
+        assert!(e.file_exists("nested/small.txt"));
+        assert!(!e.file_exists("nested/skipped.log"));
+    }
+
+    #[test]
+    fn from_dir() {
+        let source = fixture();
+        create_dir_all(source.path().join("data/empty")).unwrap();
+        write(source.path().join("data/blob.bin"), b"\x00\xffblob").unwrap();
+        let mut e = IntegrationTestEnvironment::from_dir("test", source.path().join("data"));
+        assert_eq!(
+            e.planned_entries(),
+            vec![
+                (PathBuf::from("blob.bin"), false),
+                (PathBuf::from("empty"), true),
+                (PathBuf::from("large.bin"), false),
+                (PathBuf::from("nested/small.txt"), false),
+            ]
+        );
+        e.add_file("nested/small.txt", "overridden");
+        e.setup();
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("blob.bin"),
+                PathBuf::from("empty"),
+                PathBuf::from("large.bin"),
+                PathBuf::from("nested"),
+                PathBuf::from("nested/small.txt"),
+            ]
+        );
+        assert_eq!(e.read_file("nested/small.txt"), "overridden");
+        assert_eq!(e.read_file("large.bin"), "fixture content");
+        assert_eq!(
+            std::fs::read(e.path().unwrap().join("blob.bin")).unwrap(),
+            b"\x00\xffblob"
+        );
+        assert_eq!(
+            read_to_string(source.path().join("data/nested/small.txt")).unwrap(),
+            "small"
+        );
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn from_dir_exec_mode() {
+        use std::fs::set_permissions;
+        use std::os::unix::fs::PermissionsExt;
+        let source = fixture();
+        let script = source.path().join("data/run.sh");
+        write(&script, "echo run").unwrap();
+        set_permissions(&script, PermissionsExt::from_mode(0o750)).unwrap();
+        let e = IntegrationTestEnvironment::from_dir("test", source.path().join("data"));
+        e.setup();
+        e.assert_mode("run.sh", 0o750);
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn copy_fixture_hardlink() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let source = fixture();
+        let source_file = source.path().join("data/large.bin");
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.copy_fixture(source.path().join("data"), "", CopyMode::Hardlink)
+            .unwrap();
+        e.setup();
+        e.setup();
+        let root = e.path().unwrap();
+        let ino = |path: &Path| path.metadata().unwrap().ino();
+        assert_eq!(ino(&root.join("large.bin")), ino(&source_file));
+        assert_eq!(e.read_file("large.bin"), "fixture content");
+
+        let mode = source_file.metadata().unwrap().permissions().mode();
+        e.set_exec_permission("large.bin").unwrap();
+        assert_ne!(ino(&root.join("large.bin")), ino(&source_file));
+        assert_eq!(source_file.metadata().unwrap().permissions().mode(), mode);
+
+        let small = source.path().join("data/nested/small.txt");
+        e.set_update_file_time("nested/small.txt").unwrap();
+        assert_ne!(ino(&root.join("nested/small.txt")), ino(&small));
+        assert_eq!(small.metadata().unwrap().nlink(), 1);
+        assert_eq!(read_to_string(small).unwrap(), "small");
+    }
+
+    #[test]
+    #[cfg(feature = "reflink")]
+    fn copy_fixture_reflink() {
+        let source = fixture();
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.copy_fixture(source.path().join("data"), "", CopyMode::Reflink)
+            .unwrap();
+        assert!(e.dry_run()[1].ends_with("large.bin (reflink)"));
+        e.setup();
+        assert_eq!(e.read_file("large.bin"), "fixture content");
+        write(e.path().unwrap().join("large.bin"), "modified").unwrap();
+        assert_eq!(
+            read_to_string(source.path().join("data/large.bin")).unwrap(),
+            "fixture content"
+        );
+    }
+
+    #[test]
+    fn link_or_copy_fallback() {
+        let source = fixture();
+        let dest = TempDir::new("dest").unwrap();
+        let dest = dest.path().join("copied");
+        link_or_copy(&source.path().join("data/large.bin"), &dest, |_, _| {
+            Err(io::Error::other("cross-device link"))
+        })
+        .unwrap();
+        write(&dest, "modified").unwrap();
+        assert_eq!(
+            read_to_string(source.path().join("data/large.bin")).unwrap(),
+            "fixture content"
+        );
+    }
+}
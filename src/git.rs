@@ -0,0 +1,115 @@
+use crate::IntegrationTestEnvironment;
+use fs_extra::dir::create_all;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const GIT_ENV: &[(&str, &str)] = &[
+    ("GIT_CONFIG_NOSYSTEM", "1"),
+    ("GIT_AUTHOR_NAME", "cli_integration_test"),
+    ("GIT_AUTHOR_EMAIL", "cli_integration_test@example.com"),
+    ("GIT_AUTHOR_DATE", "2000-01-01T00:00:00+00:00"),
+    ("GIT_COMMITTER_NAME", "cli_integration_test"),
+    ("GIT_COMMITTER_EMAIL", "cli_integration_test@example.com"),
+    ("GIT_COMMITTER_DATE", "2000-01-01T00:00:00+00:00"),
+];
+
+#[derive(Debug, Clone)]
+pub struct GitFixture {
+    path: PathBuf,
+}
+
+impl GitFixture {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn commit_all<M: AsRef<str>>(&self, message: M) -> &Self {
+        self.git(&["add", "--all"]);
+        self.git(&["commit", "--quiet", "--allow-empty", "-m", message.as_ref()])
+    }
+
+    pub fn create_branch<N: AsRef<str>>(&self, name: N) -> &Self {
+        self.git(&["branch", name.as_ref()])
+    }
+
+    pub fn tag<N: AsRef<str>>(&self, name: N) -> &Self {
+        self.git(&["tag", name.as_ref()])
+    }
+
+    pub fn checkout<N: AsRef<str>>(&self, name: N) -> &Self {
+        self.git(&["checkout", "--quiet", name.as_ref()])
+    }
+
+    fn git(&self, args: &[&str]) -> &Self {
+        let output = Command::new("git")
+            .args(["-c", "commit.gpgsign=false", "-c", "tag.gpgsign=false"])
+            .args(args)
+            .current_dir(&self.path)
+            .env("GIT_CONFIG_GLOBAL", null_device())
+            .envs(GIT_ENV.iter().copied())
+            .output()
+            .unwrap_or_else(|_| panic!("fail to run git {:?} in {:?}", args, self.path));
+        if !output.status.success() {
+            panic!(
+                "git {:?} failed in {:?}\n{}",
+                args,
+                self.path,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+        self
+    }
+}
+
+fn null_device() -> &'static str {
+    if cfg!(windows) {
+        "NUL"
+    } else {
+        "/dev/null"
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn init_git<P: AsRef<Path>>(&self, path: P) -> GitFixture {
+        let path = self.live_path(path.as_ref(), "create directory");
+        create_all(&path, false).unwrap_or_else(|_| panic!("fail to create directory {:?}", path));
+        let fixture = GitFixture { path };
+        fixture.git(&["init", "--quiet"]);
+        fixture.git(&["symbolic-ref", "HEAD", "refs/heads/main"]);
+        fixture
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::path::PathBuf;
+    use std::process::Command;
+
+    #[test]
+    fn init_git() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("repo/file1", "test 1");
+        e.setup();
+        let git = e.init_git("repo");
+        git.commit_all("first commit").tag("v1.0.0");
+        git.create_branch("feature").checkout("feature");
+        std::fs::write(git.path().join("file2"), "test 2").unwrap();
+        git.commit_all("second commit");
+
+        assert!(e.tree().contains(&PathBuf::from("repo/.git/HEAD")));
+        assert_eq!(
+            e.read_file("repo/.git/HEAD").trim(),
+            "ref: refs/heads/feature"
+        );
+        let log = Command::new("git")
+            .args(["log", "--format=%an %s", "main..feature"])
+            .current_dir(git.path())
+            .output()
+            .unwrap();
+        assert_eq!(
+            String::from_utf8_lossy(&log.stdout),
+            "cli_integration_test second commit\n"
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use crate::diff::unified_diff;
+use crate::IntegrationTestEnvironment;
+use fs_extra::dir::create_all;
+use std::env;
+use std::fs::{read_to_string, write};
+use std::path::{Path, PathBuf};
+
+const UPDATE_GOLDEN: &str = "UPDATE_GOLDEN";
+
+fn golden_root() -> PathBuf {
+    env::var_os("CARGO_MANIFEST_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::current_dir().expect("fail to read current directory"))
+}
+
+fn check_golden(
+    actual: &str,
+    golden_path: &Path,
+    update: bool,
+    ignore_trailing_newline: bool,
+) -> Result<(), String> {
+    if update {
+        if let Some(parent) = golden_path.parent() {
+            create_all(parent, false)
+                .unwrap_or_else(|_| panic!("fail to create directory {:?}", parent));
+        }
+        write(golden_path, actual)
+            .unwrap_or_else(|_| panic!("fail to write golden file {:?}", golden_path));
+        return Ok(());
+    }
+    let expected = read_to_string(golden_path).map_err(|_| {
+        format!(
+            "golden file {:?} does not exist, run with {}=1 to create it",
+            golden_path, UPDATE_GOLDEN
+        )
+    })?;
+    let (expected, actual) = if ignore_trailing_newline {
+        (
+            expected.trim_end_matches(&['\r', '\n'][..]),
+            actual.trim_end_matches(&['\r', '\n'][..]),
+        )
+    } else {
+        (expected.as_str(), actual)
+    };
+    if expected == actual {
+        return Ok(());
+    }
+    let mut diff = unified_diff(expected, actual, "golden", "actual");
+    if diff.is_empty() {
+        diff = "contents differ only by line endings\n".to_string();
+    }
+    Err(format!(
+        "output does not match golden file {:?}, run with {}=1 to update it\n{}",
+        golden_path, UPDATE_GOLDEN, diff
+    ))
+}
+
+impl IntegrationTestEnvironment {
+    pub fn set_golden_ignore_trailing_newline(&mut self, ignore: bool) {
+        self.golden_ignore_trailing_newline = ignore;
+    }
+
+    pub fn assert_golden<P: AsRef<Path>>(&self, actual: &str, golden_path: P) {
+        let golden_path = golden_root().join(golden_path);
+        let update = env::var_os(UPDATE_GOLDEN).is_some_and(|value| value == "1");
+        if let Err(message) = check_golden(
+            actual,
+            &golden_path,
+            update,
+            self.golden_ignore_trailing_newline,
+        ) {
+            panic!("{}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::golden::check_golden;
+    use std::fs::{read_to_string, write};
+    use tempdir::TempDir;
+
+    #[test]
+    fn check_golden_compare() {
+        let dir = TempDir::new("golden").unwrap();
+        let golden = dir.path().join("output.txt");
+        write(&golden, "line 1\nline 2\n").unwrap();
+
+        assert!(check_golden("line 1\nline 2\n", &golden, false, true).is_ok());
+        assert!(check_golden("line 1\nline 2", &golden, false, true).is_ok());
+        assert!(check_golden("line 1\nline 2", &golden, false, false).is_err());
+
+        let message = check_golden("line 1\nline two\n", &golden, false, true).unwrap_err();
+        assert!(message.contains("-line 2\n+line two\n"));
+    }
+
+    #[test]
+    fn check_golden_missing() {
+        let dir = TempDir::new("golden").unwrap();
+        let message = check_golden("", &dir.path().join("missing"), false, true).unwrap_err();
+        assert!(message.contains("UPDATE_GOLDEN=1"));
+    }
+
+    #[test]
+    fn check_golden_update() {
+        let dir = TempDir::new("golden").unwrap();
+        let golden = dir.path().join("nested/output.txt");
+        assert!(check_golden("new output\n", &golden, true, true).is_ok());
+        assert_eq!(read_to_string(&golden).unwrap(), "new output\n");
+        assert!(check_golden("new output\n", &golden, false, true).is_ok());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::IntegrationTestEnvironment;
+use std::fs::read_to_string;
+use std::io;
+use std::path::PathBuf;
+
+impl IntegrationTestEnvironment {
+    // Files that are not valid UTF-8 are skipped, the other read failures are reported.
+    pub fn grep_tree<S: AsRef<str>>(&self, needle: S) -> Vec<(PathBuf, usize, String)> {
+        self.grep_lines(|line| line.contains(needle.as_ref()))
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn grep_tree_regex(&self, regex: &regex::Regex) -> Vec<(PathBuf, usize, String)> {
+        self.grep_lines(|line| regex.is_match(line))
+    }
+
+    fn grep_lines(&self, matches: impl Fn(&str) -> bool) -> Vec<(PathBuf, usize, String)> {
+        let mut found = vec![];
+        for path in self.full_tree() {
+            let full_path = self.tmp_dir.path().join(&path);
+            // Reading a named pipe would block.
+            if !full_path.is_file() {
+                continue;
+            }
+            let content = match read_to_string(&full_path) {
+                Ok(content) => content,
+                Err(error) if error.kind() == io::ErrorKind::InvalidData => continue,
+                Err(error) => {
+                    self.report_error(format!("fail to read file {:?}: {}", full_path, error));
+                    continue;
+                }
+            };
+            for (index, line) in content.lines().enumerate() {
+                if matches(line) {
+                    found.push((path.clone(), index + 1, line.to_string()));
+                }
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::path::PathBuf;
+
+    fn environment() -> IntegrationTestEnvironment {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("src/main.rs", "fn main() {\n    TODO_REPLACE_ME\n}\n");
+        e.add_file("src/lib.rs", "pub mod generated;\n");
+        e.add_file("README", "TODO_REPLACE_ME: title\ndone\n");
+        e.setup();
+        std::fs::write(e.path().unwrap().join("binary"), [0xff, b'T', 0xfe]).unwrap();
+        e
+    }
+
+    #[test]
+    fn grep_tree() {
+        let e = environment();
+        assert_eq!(
+            e.grep_tree("TODO_REPLACE_ME"),
+            vec![
+                (
+                    PathBuf::from("README"),
+                    1,
+                    "TODO_REPLACE_ME: title".to_string()
+                ),
+                (
+                    PathBuf::from("src/main.rs"),
+                    2,
+                    "    TODO_REPLACE_ME".to_string()
+                ),
+            ]
+        );
+        assert!(e.grep_tree("missing").is_empty());
+        assert!(e.grep_tree("T").iter().all(|(path, _, _)| path != "binary"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn grep_tree_unreadable() {
+        use std::fs::{read, set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        let mut e = environment();
+        e.set_panic_on_error(false);
+        let path = e.path().unwrap().join("README");
+        set_permissions(&path, Permissions::from_mode(0o000)).unwrap();
+        // Root reads the file anyway.
+        if read(&path).is_ok() {
+            return;
+        }
+        assert_eq!(e.grep_tree("TODO_REPLACE_ME").len(), 1);
+        let errors = e.take_errors();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("fail to read file"), "{}", errors[0]);
+    }
+
+    #[test]
+    #[cfg(feature = "regex")]
+    fn grep_tree_regex() {
+        let e = environment();
+        let regex = regex::Regex::new(r"^(pub )?(fn|mod) ").unwrap();
+        let found: Vec<(PathBuf, usize)> = e
+            .grep_tree_regex(&regex)
+            .into_iter()
+            .map(|(path, line, _)| (path, line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (PathBuf::from("src/lib.rs"), 1),
+                (PathBuf::from("src/main.rs"), 1)
+            ]
+        );
+    }
+}
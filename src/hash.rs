@@ -0,0 +1,96 @@
+use crate::{display_path, permissions_mode, IntegrationTestEnvironment};
+use sha2::{Digest, Sha256};
+use std::fs::{read_link, symlink_metadata, File};
+use std::io;
+use std::path::Path;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub(crate) fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(to_hex(&hasher.finalize()))
+}
+
+impl IntegrationTestEnvironment {
+    pub fn hash_file<P: AsRef<Path>>(&self, path: P) -> String {
+        let path = self.live_path(path.as_ref(), "hash file");
+        sha256_file(&path).unwrap_or_else(|_| panic!("fail to hash file {:?}", path))
+    }
+
+    pub fn hash_tree(&self) -> String {
+        let mut hasher = Sha256::new();
+        for relative in self.full_tree() {
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+            let path = self.tmp_dir.path().join(&relative);
+            let metadata = symlink_metadata(&path)
+                .unwrap_or_else(|_| panic!("fail to read metadata {:?}", path));
+            let content = if metadata.file_type().is_symlink() {
+                let target =
+                    read_link(&path).unwrap_or_else(|_| panic!("fail to read link {:?}", path));
+                format!("link:{}", target.to_string_lossy())
+            } else if metadata.is_dir() {
+                "dir".to_string()
+            } else if !metadata.is_file() {
+                // Reading a named pipe would block.
+                "special".to_string()
+            } else {
+                format!("file:{}", self.hash_file(&relative))
+            };
+            hasher.update(display_path(&relative).as_bytes());
+            hasher.update(b"\0");
+            hasher.update(format!("{:o}", permissions_mode(&metadata)).as_bytes());
+            hasher.update(b"\0");
+            hasher.update(content.as_bytes());
+            hasher.update(b"\n");
+        }
+        to_hex(&hasher.finalize())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::fs::{create_dir, rename};
+
+    #[test]
+    fn hash_file() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("file1", "hello");
+        e.setup();
+        assert_eq!(
+            e.hash_file("file1"),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+    }
+
+    #[test]
+    fn hash_tree() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("dir/file1", "hello");
+        e.add_file("file2", "world");
+        e.setup();
+        let initial = e.hash_tree();
+        assert_eq!(initial, e.hash_tree());
+
+        let root = e.path().unwrap();
+        rename(root.join("file2"), root.join("file3")).unwrap();
+        let renamed = e.hash_tree();
+        assert_ne!(initial, renamed);
+
+        #[cfg(unix)]
+        {
+            e.make_executable("file3").unwrap();
+            assert_ne!(renamed, e.hash_tree());
+        }
+
+        let before_dir = e.hash_tree();
+        create_dir(root.join("empty_dir")).unwrap();
+        assert_ne!(before_dir, e.hash_tree());
+    }
+}
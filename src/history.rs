@@ -0,0 +1,89 @@
+use crate::invocation::invocation_line;
+use crate::{format_output, IntegrationTestEnvironment};
+use std::cell::Ref;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Output};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct CommandRecord {
+    pub program: String,
+    pub args: Vec<String>,
+    pub cwd: PathBuf,
+    // Sorted by name, only the variables set on the environment.
+    pub envs: Vec<(String, String)>,
+    pub duration: Duration,
+    pub status: ExitStatus,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+impl CommandRecord {
+    pub fn invocation(&self) -> String {
+        invocation_line(&self.program, &self.args)
+    }
+}
+
+impl IntegrationTestEnvironment {
+    // Off by default, every recorded command keeps its whole output.
+    pub fn enable_history(&mut self) {
+        self.history_enabled = true;
+    }
+
+    // Only commands run through `run` and `run_timed` are recorded.
+    pub fn history(&self) -> Ref<'_, [CommandRecord]> {
+        Ref::map(self.history.borrow(), |history| history.as_slice())
+    }
+
+    pub(crate) fn record(&self, program: &str, args: &[&str], output: &Output, duration: Duration) {
+        if !self.history_enabled {
+            return;
+        }
+        let mut envs: Vec<(String, String)> = self
+            .envs
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+        envs.sort();
+        self.history.borrow_mut().push(CommandRecord {
+            program: program.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            cwd: self.canonical_path(),
+            envs,
+            duration,
+            status: output.status,
+            stdout: output.stdout.clone(),
+            stderr: output.stderr.clone(),
+        });
+    }
+
+    pub fn format_history(&self) -> String {
+        let mut report = String::new();
+        for (index, record) in self.history().iter().enumerate() {
+            if index > 0 {
+                report.push_str("===========================\n");
+            }
+            report.push_str(&format!(
+                "[{}] $ {} ({:?})\n",
+                index,
+                record.invocation(),
+                record.duration
+            ));
+            for (key, value) in &record.envs {
+                report.push_str(&format!("{}={}\n", key, value));
+            }
+            let output = Output {
+                status: record.status,
+                stdout: record.stdout.clone(),
+                stderr: record.stderr.clone(),
+            };
+            report.push_str(&format_output(&output));
+            report.push('\n');
+        }
+        report
+    }
+
+    pub fn print_history(&self) {
+        print!("{}", self.format_history());
+    }
+}
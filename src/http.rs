@@ -0,0 +1,227 @@
+use crate::IntegrationTestEnvironment;
+use std::fs::read;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+pub const HTTP_URL_ENV: &str = "CLI_INTEGRATION_TEST_HTTP_URL";
+const HTTP_ROOT: &str = "www";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub status: u16,
+}
+
+impl RecordedRequest {
+    pub fn header<N: AsRef<str>>(&self, name: N) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name.as_ref()))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub struct HttpFixture {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    shutdown: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl HttpFixture {
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+impl Drop for HttpFixture {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the blocking accept so the server thread sees the shutdown flag.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn resolve(root: &Path, request_path: &str) -> Option<PathBuf> {
+    let request_path = request_path.split(['?', '#']).next().unwrap_or_default();
+    let mut path = root.to_path_buf();
+    for component in Path::new(request_path.trim_start_matches('/')).components() {
+        match component {
+            Component::Normal(component) => path.push(component),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    Some(path)
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => "application/json",
+        Some("html") => "text/html",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    root: &Path,
+    requests: &Mutex<Vec<RecordedRequest>>,
+) -> io::Result<()> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut headers = vec![];
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    let (status, reason, body, content_type) = if method != "GET" && method != "HEAD" {
+        (405, "Method Not Allowed", vec![], "text/plain")
+    } else {
+        match resolve(root, &path).filter(|path| path.is_file()) {
+            Some(file) => match read(&file) {
+                Ok(body) => (200, "OK", body, content_type(&file)),
+                Err(_) => (500, "Internal Server Error", vec![], "text/plain"),
+            },
+            None => (404, "Not Found", vec![], "text/plain"),
+        }
+    };
+
+    // Recorded before responding, a client reading the response then `requests()`
+    // must see its own request.
+    requests.lock().unwrap().push(RecordedRequest {
+        method: method.clone(),
+        path,
+        headers,
+        status,
+    });
+
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        reason,
+        content_type,
+        body.len()
+    )?;
+    if method != "HEAD" {
+        stream.write_all(&body)?;
+    }
+    stream.flush()
+}
+
+impl IntegrationTestEnvironment {
+    pub fn start_http_server(&mut self) -> HttpFixture {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("fail to bind http server");
+        let addr = listener
+            .local_addr()
+            .expect("fail to read http server address");
+        let requests = Arc::new(Mutex::new(vec![]));
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let root = self.tmp_dir.path().join(HTTP_ROOT);
+
+        let handle = {
+            let requests = Arc::clone(&requests);
+            let shutdown = Arc::clone(&shutdown);
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        let _ = handle_connection(stream, &root, &requests);
+                    }
+                }
+            })
+        };
+
+        let fixture = HttpFixture {
+            addr,
+            requests,
+            shutdown,
+            handle: Some(handle),
+        };
+        self.envs.insert(HTTP_URL_ENV.to_string(), fixture.url());
+        fixture
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::io::{Read, Write};
+    use std::net::TcpStream;
+
+    fn get(url: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(url.trim_start_matches("http://")).unwrap();
+        write!(
+            stream,
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nX-Test: yes\r\n\r\n",
+            path
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn start_http_server() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("www/manifest.json", r#"{"version":"1.0.0"}"#);
+        e.setup();
+        let server = e.start_http_server();
+
+        let response = get(&server.url(), "/manifest.json");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("Content-Type: application/json\r\n"));
+        assert!(response.ends_with(r#"{"version":"1.0.0"}"#));
+
+        let response = get(&server.url(), "/missing.json");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        let response = get(&server.url(), "/../escape");
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].method, "GET");
+        assert_eq!(requests[0].path, "/manifest.json");
+        assert_eq!(requests[0].status, 200);
+        assert_eq!(requests[0].header("x-test"), Some("yes"));
+        assert_eq!(requests[1].path, "/missing.json");
+        assert_eq!(requests[1].status, 404);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{display_path, IntegrationTestEnvironment};
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+// Like `.gitignore`: a pattern without `/` matches a file name at any depth, otherwise
+// the whole relative path. The content of an ignored directory is ignored too.
+#[derive(Clone)]
+pub(crate) struct IgnorePattern {
+    pattern: Pattern,
+    anchored: bool,
+}
+
+pub(crate) fn is_ignored(patterns: &[IgnorePattern], relative: &Path) -> bool {
+    if relative.as_os_str().is_empty() {
+        return false;
+    }
+    let path = display_path(relative);
+    let name = relative
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    patterns.iter().any(|ignore| {
+        let candidate = if ignore.anchored { &path } else { &name };
+        ignore.pattern.matches_with(candidate, MATCH_OPTIONS)
+    })
+}
+
+impl IntegrationTestEnvironment {
+    pub fn set_tree_ignore(&mut self, patterns: Vec<String>) {
+        self.tree_ignore = patterns
+            .iter()
+            .map(|pattern| IgnorePattern {
+                pattern: Pattern::new(pattern.trim_start_matches('/')).unwrap_or_else(|error| {
+                    panic!("invalid ignore pattern {:?}: {}", pattern, error)
+                }),
+                anchored: pattern.contains('/'),
+            })
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::path::PathBuf;
+
+    #[test]
+    fn tree_ignore() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("out/result.txt", "result");
+        e.add_file("out/cargo.lock", "");
+        e.add_file(".cache/data", "");
+        e.add_file("src/.cache", "");
+        e.set_tree_ignore(vec!["*.lock".to_string(), "/.cache".to_string()]);
+        e.setup();
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("out"),
+                PathBuf::from("out/result.txt"),
+                PathBuf::from("src"),
+                PathBuf::from("src/.cache"),
+            ]
+        );
+        assert_eq!(
+            e.tree_files(),
+            vec![PathBuf::from("out/result.txt"), PathBuf::from("src/.cache")]
+        );
+        assert_eq!(e.to_string(), "\nout\nout/result.txt\nsrc\nsrc/.cache\n");
+
+        let clone = e.clone_plan("clone");
+        clone.setup();
+        assert_eq!(clone.tree(), e.tree());
+    }
+
+    #[test]
+    fn tree_ignore_only_applies_to_listings() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("out/result.txt", "result");
+        e.add_file("out/cargo.lock", "locked");
+        e.set_tree_ignore(vec!["*.lock".to_string()]);
+        e.setup();
+        let lock = PathBuf::from("out/cargo.lock");
+        assert_eq!(
+            e.grep_tree("locked"),
+            vec![(lock.clone(), 1, "locked".to_string())]
+        );
+        assert!(e
+            .tree_with_metadata()
+            .iter()
+            .any(|entry| entry.path == lock));
+
+        let snapshot = e.capture();
+        let hash = e.hash_tree();
+        e.write_file("out/cargo.lock", "relocked").unwrap();
+        assert_eq!(e.changes_since(&snapshot).modified, vec![lock]);
+        assert_ne!(e.hash_tree(), hash);
+
+        let other = IntegrationTestEnvironment::new("other");
+        other.setup();
+        assert!(e.diff_with(&other).to_string().contains("out/cargo.lock"));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::IntegrationTestEnvironment;
+use std::io::{Read, Write};
+use std::process::{Child, ChildStdin, Output, Stdio};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+// Pipes only, no terminal: the CLI under test has to flush its prompts. Stdout is read
+// in chunks, so a prompt without a trailing newline can still be matched.
+pub struct InteractiveSession {
+    child: Option<Child>,
+    stdin: Option<ChildStdin>,
+    chunks: Receiver<Vec<u8>>,
+    stderr: Option<JoinHandle<Vec<u8>>>,
+    stdout: Vec<u8>,
+    // Every `expect` searches after the end of the previous match.
+    cursor: usize,
+}
+
+impl InteractiveSession {
+    pub fn expect(&mut self, pattern: &str, timeout: Duration) {
+        self.expect_with(pattern, timeout, |stdout| {
+            stdout.find(pattern).map(|start| start + pattern.len())
+        });
+    }
+
+    #[cfg(feature = "regex")]
+    pub fn expect_regex(&mut self, pattern: &regex::Regex, timeout: Duration) {
+        self.expect_with(pattern.as_str(), timeout, |stdout| {
+            pattern.find(stdout).map(|found| found.end())
+        });
+    }
+
+    fn expect_with<F>(&mut self, pattern: &str, timeout: Duration, find: F)
+    where
+        F: Fn(&str) -> Option<usize>,
+    {
+        let start = Instant::now();
+        loop {
+            let unread = String::from_utf8_lossy(&self.stdout[self.cursor..]).into_owned();
+            if let Some(end) = find(&unread) {
+                // `end` is an offset in the lossy string, it matches the bytes as long as
+                // stdout is valid UTF-8.
+                self.cursor += end;
+                return;
+            }
+            let remaining = timeout.saturating_sub(start.elapsed());
+            match self.chunks.recv_timeout(remaining) {
+                Ok(chunk) => self.stdout.extend(chunk),
+                Err(RecvTimeoutError::Timeout) => panic!(
+                    "fail to find {:?} within {:?}\n{}",
+                    pattern,
+                    timeout,
+                    self.transcript()
+                ),
+                Err(RecvTimeoutError::Disconnected) => panic!(
+                    "fail to find {:?}: stdout was closed\n{}",
+                    pattern,
+                    self.transcript()
+                ),
+            }
+        }
+    }
+
+    pub fn send_line(&mut self, line: &str) {
+        let stdin = self.stdin.as_mut().expect("stdin is closed");
+        writeln!(stdin, "{}", line)
+            .and_then(|_| stdin.flush())
+            .unwrap_or_else(|error| {
+                panic!("fail to send {:?}: {}\n{}", line, error, self.transcript())
+            });
+    }
+
+    // Closes stdin, waits for the process to exit and returns everything it wrote.
+    pub fn finish(mut self) -> Output {
+        drop(self.stdin.take());
+        let status = self
+            .child
+            .take()
+            .expect("the session is already finished")
+            .wait()
+            .expect("fail to wait for the interactive process");
+        while let Ok(chunk) = self.chunks.recv() {
+            self.stdout.extend(chunk);
+        }
+        let stderr = self
+            .stderr
+            .take()
+            .and_then(|stderr| stderr.join().ok())
+            .unwrap_or_default();
+        Output {
+            status,
+            stdout: std::mem::take(&mut self.stdout),
+            stderr,
+        }
+    }
+
+    fn transcript(&self) -> String {
+        format!(
+            "---------------------------\n{}\n---------------------------",
+            String::from_utf8_lossy(&self.stdout)
+        )
+    }
+}
+
+impl Drop for InteractiveSession {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn interact<C: AsRef<str>>(&self, crate_name: C) -> InteractiveSession {
+        let mut child = self
+            .spawnable_command(crate_name.as_ref())
+            .unwrap_or_else(|error| panic!("{}", error))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap_or_else(|error| panic!("fail to run {:?}: {}", crate_name.as_ref(), error));
+        let mut stdout = child.stdout.take().expect("stdout is piped");
+        let (sender, chunks) = channel();
+        thread::spawn(move || {
+            let mut buffer = [0; 4096];
+            while let Ok(read) = stdout.read(&mut buffer) {
+                if read == 0 || sender.send(buffer[..read].to_vec()).is_err() {
+                    break;
+                }
+            }
+        });
+        let mut stderr = child.stderr.take().expect("stderr is piped");
+        let stderr = thread::spawn(move || {
+            let mut content = vec![];
+            let _ = stderr.read_to_end(&mut content);
+            content
+        });
+        InteractiveSession {
+            stdin: child.stdin.take(),
+            child: Some(child),
+            chunks,
+            stderr: Some(stderr),
+            stdout: vec![],
+            cursor: 0,
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use crate::paths::normalize;
+use crate::{format_output, IntegrationTestEnvironment};
+use assert_cmd::Command;
+use std::env::consts::EXE_SUFFIX;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::Duration;
+
+// The duration is only known once the command ran through `timed_run`.
+pub(crate) struct Invocation {
+    line: String,
+    duration: Option<Duration>,
+}
+
+// POSIX shell quoting, arguments made of safe characters only are left as they are.
+pub(crate) fn shell_quote(arg: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+pub(crate) fn invocation_line<S: AsRef<OsStr>>(program: &str, args: &[S]) -> String {
+    let mut line = shell_quote(program);
+    for arg in args {
+        line.push(' ');
+        line.push_str(&shell_quote(&arg.as_ref().to_string_lossy()));
+    }
+    line
+}
+
+// Named like the binary, the way `command_with_args` names it after the crate.
+fn command_invocation(command: &Command) -> String {
+    let program = Path::new(command.get_program())
+        .file_name()
+        .unwrap_or(command.get_program())
+        .to_string_lossy();
+    let program = program.strip_suffix(EXE_SUFFIX).unwrap_or(&program);
+    let args: Vec<&OsStr> = command.get_args().collect();
+    invocation_line(program, &args)
+}
+
+impl IntegrationTestEnvironment {
+    pub fn command_with_args<C, I, S>(&self, crate_name: C, args: I) -> Command
+    where
+        C: AsRef<str>,
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args: Vec<S> = args.into_iter().collect();
+        let mut command = self
+            .command(crate_name.as_ref())
+            .expect("fail to create command");
+        command.args(&args);
+        *self.last_invocation.borrow_mut() = Some(Invocation {
+            line: invocation_line(crate_name.as_ref(), &args),
+            duration: None,
+        });
+        command
+    }
+
+    // Every argument is a path relative to the environment root, passed to the child
+    // joined to the canonical root.
+    pub fn command_with_path_args<C: AsRef<str>>(
+        &self,
+        crate_name: C,
+        rel_args: &[&str],
+    ) -> Command {
+        let root = self.canonical_path();
+        let args: Vec<PathBuf> = rel_args
+            .iter()
+            .map(|arg| {
+                let relative = normalize(Path::new(arg)).unwrap_or_else(|reason| {
+                    panic!("fail to resolve argument {:?}: {}", arg, reason)
+                });
+                root.join(relative)
+            })
+            .collect();
+        self.command_with_args(crate_name, args)
+    }
+
+    // Commands built by `command_with_args` or `command_args`, or run by `timed_run`.
+    pub fn last_invocation(&self) -> Option<String> {
+        self.last_invocation
+            .borrow()
+            .as_ref()
+            .map(|invocation| invocation.line.clone())
+    }
+
+    // Both are recorded together, a duration never belongs to another command.
+    pub(crate) fn record_timed_invocation(&self, command: &Command, duration: Duration) {
+        *self.last_invocation.borrow_mut() = Some(Invocation {
+            line: command_invocation(command),
+            duration: Some(duration),
+        });
+    }
+
+    pub fn format_last_output(&self, output: &Output) -> String {
+        format!("{}{}", self.invocation_report(), format_output(output))
+    }
+
+    // The `$ program args` line of the last command, with its duration once it ran
+    // through `timed_run`.
+    pub(crate) fn invocation_report(&self) -> String {
+        let last_invocation = self.last_invocation.borrow();
+        let invocation = match last_invocation.as_ref() {
+            Some(invocation) => invocation,
+            None => return String::new(),
+        };
+        let mut report = format!("$ {}", invocation.line);
+        if let Some(duration) = invocation.duration {
+            report.push_str(&format!(" (took {:?})", duration));
+        }
+        report.push('\n');
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::shell_quote;
+    use crate::IntegrationTestEnvironment;
+
+    #[test]
+    fn quote() {
+        assert_eq!(shell_quote("plain-arg_1.txt"), "plain-arg_1.txt");
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("two words"), "'two words'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("$HOME"), "'$HOME'");
+    }
+
+    #[test]
+    fn last_invocation() {
+        let e = IntegrationTestEnvironment::new("test");
+        assert_eq!(e.last_invocation(), None);
+    }
+}
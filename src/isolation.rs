@@ -0,0 +1,53 @@
+use crate::IntegrationTestEnvironment;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XdgDirs {
+    config: PathBuf,
+    data: PathBuf,
+    cache: PathBuf,
+}
+
+impl XdgDirs {
+    pub fn config(&self) -> &Path {
+        &self.config
+    }
+
+    pub fn data(&self) -> &Path {
+        &self.data
+    }
+
+    pub fn cache(&self) -> &Path {
+        &self.cache
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn isolate_home(&mut self) -> PathBuf {
+        self.add_dir("home");
+        let home = self.tmp_dir.path().join("home");
+        self.set_env("HOME", home.to_string_lossy());
+        if cfg!(windows) {
+            self.set_env("USERPROFILE", home.to_string_lossy());
+        }
+        home
+    }
+
+    pub fn isolate_xdg(&mut self) -> XdgDirs {
+        let dirs = XdgDirs {
+            config: PathBuf::from("xdg/config"),
+            data: PathBuf::from("xdg/data"),
+            cache: PathBuf::from("xdg/cache"),
+        };
+        for (key, dir) in [
+            ("XDG_CONFIG_HOME", &dirs.config),
+            ("XDG_DATA_HOME", &dirs.data),
+            ("XDG_CACHE_HOME", &dirs.cache),
+        ] {
+            self.add_dir(dir);
+            let value = self.tmp_dir.path().join(dir);
+            self.set_env(key, value.to_string_lossy());
+        }
+        dirs
+    }
+}
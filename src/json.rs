@@ -0,0 +1,56 @@
+use crate::IntegrationTestEnvironment;
+use serde::de::DeserializeOwned;
+use std::path::Path;
+
+impl IntegrationTestEnvironment {
+    pub fn read_file_json<P, T>(&self, path: P) -> T
+    where
+        P: AsRef<Path>,
+        T: DeserializeOwned,
+    {
+        let content = self.read_file(path.as_ref());
+        serde_json::from_str(&content)
+            .unwrap_or_else(|error| panic!("fail to parse json {:?}: {}", path.as_ref(), error))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Report {
+        version: String,
+        passed: u32,
+        tags: Vec<String>,
+    }
+
+    #[test]
+    fn read_file_json() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file(
+            "out/report.json",
+            r#"{"tags": ["fast"], "passed": 3, "version": "1.0.0", "extra": null}"#,
+        );
+        e.setup();
+        let report: Report = e.read_file_json("out/report.json");
+        assert_eq!(
+            report,
+            Report {
+                version: "1.0.0".to_string(),
+                passed: 3,
+                tags: vec!["fast".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to parse json \"invalid.json\"")]
+    fn read_file_json_invalid() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("invalid.json", "{");
+        e.setup();
+        let _: Report = e.read_file_json("invalid.json");
+    }
+}
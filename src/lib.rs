@@ -1,21 +1,128 @@
+use crate::entry::{ContentPreview, Entry, Generator};
+use crate::fixture::copy_entry;
+use crate::invocation::Invocation;
+use crate::paths::{checked_path, normalize};
+use assert_cmd::cargo::CommandCargoExt;
 use assert_cmd::Command;
 use fs_extra::dir::create_all;
 use fs_extra::file::read_to_string;
-use fs_extra::file::write_all;
-use std::collections::HashMap;
-use std::fmt::{Display, Formatter};
-use std::fs::{set_permissions, OpenOptions, Permissions};
+use std::cell::{Ref, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Debug, Display, Formatter};
+use std::fs::{hard_link, write, Metadata, OpenOptions};
 use std::io;
 use std::io::Write as IoWrite;
-use std::os::unix::fs::PermissionsExt;
+use std::net::TcpListener;
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, SystemTime};
 use tempdir::TempDir;
 use walkdir::WalkDir;
 
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(feature = "archive")]
+mod archive_reader;
+mod artifact;
+mod assert;
+mod background;
+mod builder;
+#[cfg(feature = "colors")]
+mod colors;
+mod diff;
+mod entry;
+mod error;
+mod export;
+mod fifo;
+mod fixture;
+#[cfg(feature = "git")]
+mod git;
+mod golden;
+mod grep;
+mod hash;
+mod history;
+#[cfg(feature = "http")]
+mod http;
+mod ignore;
+mod interactive;
+mod invocation;
+mod isolation;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+mod manifest;
+mod newline;
+mod output;
+mod paths;
+mod plan;
+mod ports;
+mod process;
+mod search_path;
+mod snapshot;
+#[cfg(unix)]
+mod socket;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod tree;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "archive")]
+pub use archive_reader::{ArchiveContents, ArchiveEntry};
+pub use background::BackgroundProcess;
+pub use builder::{IntegrationTestEnvironmentBuilder, ReadyEnv, ReadyEnvBuilder};
+pub use diff::EnvDiff;
+pub use entry::{EntryKind, OverwritePolicy};
+pub use error::CargoBinError;
+pub use fixture::CopyMode;
+#[cfg(feature = "git")]
+pub use git::GitFixture;
+pub use history::CommandRecord;
+#[cfg(feature = "http")]
+pub use http::{HttpFixture, RecordedRequest, HTTP_URL_ENV};
+pub use interactive::InteractiveSession;
+pub use isolation::XdgDirs;
+#[cfg(feature = "serde")]
+pub use manifest::{Manifest, ManifestEntry, ManifestKind};
+pub use newline::NewlinePolicy;
+pub use output::{
+    assert_output, assert_output_matches, format_output, CommandOutcome, OutputExt, OutputMatch,
+};
+pub use plan::PlannedOp;
+pub use search_path::PathMode;
+pub use snapshot::{Changes, TreeSnapshot};
+pub use tree::{render_debug_tree, TreeEntry};
+#[cfg(feature = "watch")]
+pub use watch::{ChangeEvent, ChangeKind, TimeoutError};
+
+type CommandCallback = dyn Fn(&str, &Path, Command) -> io::Result<Command>;
+type SetupHook = dyn Fn(&Path);
+
 pub struct IntegrationTestEnvironment {
+    label: String,
     tmp_dir: TempDir,
-    entries: HashMap<PathBuf, Option<String>>,
-    cfg_command_callback: Box<dyn Fn(PathBuf, Command) -> Command>,
+    entries: HashMap<PathBuf, Entry>,
+    cfg_command_callback: Box<CommandCallback>,
+    post_setup_hook: Option<Box<SetupHook>>,
+    golden_ignore_trailing_newline: bool,
+    envs: HashMap<String, String>,
+    modes: HashMap<PathBuf, u32>,
+    path_dirs: Vec<(PathBuf, PathMode)>,
+    panic_on_error: bool,
+    errors: RefCell<Vec<String>>,
+    last_invocation: RefCell<Option<Invocation>>,
+    concurrent_timeout: Duration,
+    bin_paths: RefCell<HashMap<String, PathBuf>>,
+    tree_ignore: Vec<ignore::IgnorePattern>,
+    ports: HashMap<u16, Option<TcpListener>>,
+    // Only unix has `socket_path()`.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    socket_dir: RefCell<Option<TempDir>>,
+    history_enabled: bool,
+    history: RefCell<Vec<CommandRecord>>,
+    newline_policy: NewlinePolicy,
+    overwrite_policy: OverwritePolicy,
 }
 
 impl IntegrationTestEnvironment {
@@ -23,20 +130,149 @@ impl IntegrationTestEnvironment {
     where
         L: AsRef<str>,
     {
-        let label = label.as_ref().to_string();
-        let tmp_dir = TempDir::new(&label).expect("fail to create tmp directory");
-        Self {
+        Self::try_new(label).expect("fail to create tmp directory")
+    }
+
+    pub fn try_new<L>(label: L) -> io::Result<Self>
+    where
+        L: AsRef<str>,
+    {
+        let prefix = tmp_dir_prefix(label.as_ref());
+        Self::try_new_with_prefix(label, prefix)
+    }
+
+    pub fn new_with_prefix<L, P>(label: L, prefix: P) -> Self
+    where
+        L: AsRef<str>,
+        P: AsRef<str>,
+    {
+        Self::try_new_with_prefix(label, prefix).expect("fail to create tmp directory")
+    }
+
+    pub fn try_new_with_prefix<L, P>(label: L, prefix: P) -> io::Result<Self>
+    where
+        L: AsRef<str>,
+        P: AsRef<str>,
+    {
+        let tmp_dir = TempDir::new(prefix.as_ref())?;
+        Ok(Self {
+            label: label.as_ref().to_string(),
             tmp_dir,
             entries: HashMap::new(),
-            cfg_command_callback: Box::new(|_, c| c),
-        }
+            cfg_command_callback: Box::new(|_, _, c| Ok(c)),
+            post_setup_hook: None,
+            golden_ignore_trailing_newline: true,
+            envs: HashMap::new(),
+            modes: HashMap::new(),
+            path_dirs: vec![],
+            panic_on_error: true,
+            errors: RefCell::new(vec![]),
+            last_invocation: RefCell::new(None),
+            concurrent_timeout: process::CONCURRENT_TIMEOUT,
+            bin_paths: RefCell::new(HashMap::new()),
+            tree_ignore: vec![],
+            ports: HashMap::new(),
+            socket_dir: RefCell::new(None),
+            history_enabled: false,
+            history: RefCell::new(vec![]),
+            newline_policy: NewlinePolicy::Preserve,
+            overwrite_policy: OverwritePolicy::LastWins,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn set_command_callback(
+        &mut self,
+        callback: impl Fn(&str, &Path, Command) -> Command + 'static,
+    ) {
+        self.cfg_command_callback =
+            Box::new(move |crate_name, path, command| Ok(callback(crate_name, path, command)));
+    }
+
+    // Called with the environment root at the end of every `setup()`, for fixtures the
+    // entries can not describe (device nodes, sockets, ...).
+    pub fn set_post_setup_hook(&mut self, hook: impl Fn(&Path) + 'static) {
+        self.post_setup_hook = Some(Box::new(hook));
     }
 
+    // Kept for callbacks written before the crate name was passed along,
+    // prefer `set_command_callback`.
     pub fn set_cfg_command_callback(
         &mut self,
         callback: impl Fn(PathBuf, Command) -> Command + 'static,
     ) {
-        self.cfg_command_callback = Box::new(callback);
+        self.set_command_callback(move |_, path, command| callback(path.to_path_buf(), command));
+    }
+
+    // The error is returned by `try_command` and `command`.
+    pub fn set_cfg_command_callback_result(
+        &mut self,
+        callback: impl Fn(&Path, Command) -> io::Result<Command> + 'static,
+    ) {
+        self.cfg_command_callback = Box::new(move |_, path, command| callback(path, command));
+    }
+
+    // When disabled, operations that would panic record the error instead, to be
+    // collected with `take_errors`, and carry on with an empty result.
+    pub fn set_panic_on_error(&mut self, panic_on_error: bool) {
+        self.panic_on_error = panic_on_error;
+    }
+
+    pub fn take_errors(&self) -> Vec<String> {
+        self.errors.take()
+    }
+
+    pub(crate) fn report_error(&self, error: String) {
+        self.report_errors(vec![error]);
+    }
+
+    pub(crate) fn report_errors(&self, errors: Vec<String>) {
+        if errors.is_empty() {
+            return;
+        }
+        if self.panic_on_error {
+            panic!("{}", errors.join("\n"));
+        }
+        self.errors.borrow_mut().extend(errors);
+    }
+
+    pub fn set_overwrite_policy(&mut self, policy: OverwritePolicy) {
+        self.overwrite_policy = policy;
+    }
+
+    // Adding a directory again is never a conflict, the intermediate directories of
+    // `add_dir` may already be registered.
+    pub(crate) fn stage(&mut self, path: PathBuf, entry: Entry) {
+        let path = self.entry_path(&path);
+        if let Some(existing) = self.entries.get(&path) {
+            if !(existing.is_dir() && entry.is_dir()) {
+                match self.overwrite_policy {
+                    OverwritePolicy::LastWins => {}
+                    OverwritePolicy::FirstWins => return,
+                    OverwritePolicy::Panic => panic!(
+                        "fail to add {:?}: already added as {}, added again as {}",
+                        path,
+                        existing.describe(),
+                        entry.describe()
+                    ),
+                }
+            }
+        }
+        self.entries.insert(path, entry);
+    }
+
+    // Staging a path outside of the environment is a mistake in the test itself.
+    pub(crate) fn entry_path(&self, path: &Path) -> PathBuf {
+        normalize(path).unwrap_or_else(|reason| panic!("fail to add {:?}: {}", path, reason))
+    }
+
+    pub(crate) fn live_path(&self, path: &Path, action: &str) -> PathBuf {
+        let relative = normalize(path)
+            .unwrap_or_else(|reason| panic!("fail to {} {:?}: {}", action, path, reason));
+        self.tmp_dir.path().join(relative)
     }
 
     pub fn add_file<P, C>(&mut self, path: P, content: C)
@@ -44,67 +280,521 @@ impl IntegrationTestEnvironment {
         P: AsRef<Path>,
         C: AsRef<str>,
     {
-        self.entries.insert(
+        self.stage(
+            path.as_ref().to_path_buf(),
+            Entry::File(content.as_ref().to_string()),
+        );
+    }
+
+    // The mode is applied by `setup()` on unix, the file is a plain file elsewhere.
+    pub fn add_executable_file<P, C>(&mut self, path: P, content: C)
+    where
+        P: AsRef<Path>,
+        C: AsRef<str>,
+    {
+        let path = self.entry_path(path.as_ref());
+        self.add_file(&path, content);
+        self.modes.insert(path, 0o755);
+    }
+
+    pub fn add_empty_file<P: AsRef<Path>>(&mut self, path: P) {
+        self.add_file(path, "");
+    }
+
+    // The generator runs once, at the first `setup()`, its content is reused afterwards.
+    pub fn add_file_with<P, F>(&mut self, path: P, generator: F)
+    where
+        P: AsRef<Path>,
+        F: FnOnce() -> String + 'static,
+    {
+        self.stage(
             path.as_ref().to_path_buf(),
-            Some(content.as_ref().to_string()),
+            Entry::Generated(Rc::new(Generator::new(generator))),
         );
     }
 
+    pub fn add_hardlink<P, Q>(&mut self, link: P, target: Q)
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        let target = self.entry_path(target.as_ref());
+        self.stage(link.as_ref().to_path_buf(), Entry::Hardlink(target));
+    }
+
     pub fn read_file<P>(&self, path: P) -> String
     where
         P: AsRef<Path>,
     {
-        let path = self.tmp_dir.path().join(path.as_ref());
-        read_to_string(&path).expect(format!("fail to read file {:?}", path).as_str())
+        let path = match normalize(path.as_ref()) {
+            Ok(path) => self.tmp_dir.path().join(path),
+            Err(reason) => {
+                self.report_error(format!("fail to read file {:?}: {}", path.as_ref(), reason));
+                return String::new();
+            }
+        };
+        match read_to_string(&path) {
+            Ok(content) => self.newline_policy.apply(content),
+            Err(_) => {
+                self.report_error(format!("fail to read file {:?}", path));
+                String::new()
+            }
+        }
+    }
+
+    pub fn read_lines<P: AsRef<Path>>(&self, path: P) -> Vec<String> {
+        let content = self.read_file(path);
+        let content = content.strip_suffix('\n').unwrap_or(&content);
+        if content.is_empty() {
+            return vec![];
+        }
+        content
+            .split('\n')
+            .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+            .collect()
+    }
+
+    // Lines are numbered from 0.
+    pub fn read_line<P: AsRef<Path>>(&self, path: P, n: usize) -> String {
+        let mut lines = self.read_lines(path.as_ref());
+        if n >= lines.len() {
+            panic!(
+                "fail to read line {} of {:?}: the file has {} lines",
+                n,
+                path.as_ref(),
+                lines.len()
+            );
+        }
+        lines.swap_remove(n)
     }
 
     pub fn file_exists<P: AsRef<Path>>(&self, path: P) -> bool {
-        let path = self.tmp_dir.path().join(path.as_ref());
-        path.exists()
+        match checked_path(path.as_ref()) {
+            Ok(path) => self.tmp_dir.path().join(path).exists(),
+            Err(error) => {
+                self.report_error(format!("fail to check file {}", error));
+                false
+            }
+        }
+    }
+
+    pub fn file_size<P: AsRef<Path>>(&self, path: P) -> u64 {
+        let path = match checked_path(path.as_ref()) {
+            Ok(path) => self.tmp_dir.path().join(path),
+            Err(error) => {
+                self.report_error(format!("fail to read metadata {}", error));
+                return 0;
+            }
+        };
+        match path.metadata() {
+            Ok(metadata) => metadata.len(),
+            Err(_) => {
+                self.report_error(format!("fail to read metadata {:?}", path));
+                0
+            }
+        }
+    }
+
+    pub fn file_is_empty<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.file_size(path) == 0
+    }
+
+    pub fn write_file<P, C>(&self, path: P, content: C) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+        C: AsRef<str>,
+    {
+        let path = checked_path(path.as_ref())?;
+        self.detach(&path)?;
+        let path = self.tmp_dir.path().join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        write(path, content.as_ref())
+    }
+
+    // Creates the file if needed, an existing file keeps its content and gets a new mtime.
+    pub fn touch<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = checked_path(path.as_ref())?;
+        self.detach(&path)?;
+        let path = self.tmp_dir.path().join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        file.set_modified(SystemTime::now())
+    }
+
+    pub fn delete_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        std::fs::remove_file(self.tmp_dir.path().join(checked_path(path.as_ref())?))
     }
 
     pub fn add_dir<P>(&mut self, path: P)
     where
         P: AsRef<Path>,
     {
-        self.entries.insert(path.as_ref().to_path_buf(), None);
+        let path = self.entry_path(path.as_ref());
+        // Intermediate directories are part of the plan too, without replacing an
+        // entry already staged at their path.
+        for ancestor in path.ancestors().skip(1) {
+            if !ancestor.as_os_str().is_empty() {
+                self.entries
+                    .entry(ancestor.to_path_buf())
+                    .or_insert(Entry::Dir);
+            }
+        }
+        self.stage(path, Entry::Dir);
+    }
+
+    // Entries staged in `other` override the ones with the same path, the files
+    // generated by `add_file_with` are shared and still generated only once.
+    pub fn merge_plan(&mut self, other: &IntegrationTestEnvironment) {
+        for (path, entry) in other.entries.iter() {
+            self.entries.insert(path.clone(), entry.clone());
+        }
+        for (path, mode) in other.modes.iter() {
+            self.modes.insert(path.clone(), *mode);
+        }
+    }
+
+    // A fresh temporary directory with the same plan and configuration. Callbacks and
+    // hooks can not be cloned, the new environment has none, and what commands recorded
+    // or reserved stays with `self`. Every field is named so that a new one is not
+    // forgotten here.
+    pub fn clone_plan(&self, new_label: &str) -> IntegrationTestEnvironment {
+        let IntegrationTestEnvironment {
+            label: _,
+            tmp_dir: _,
+            entries: _,
+            cfg_command_callback: _,
+            post_setup_hook: _,
+            golden_ignore_trailing_newline,
+            envs,
+            modes: _,
+            path_dirs,
+            panic_on_error,
+            errors: _,
+            last_invocation: _,
+            concurrent_timeout,
+            bin_paths,
+            tree_ignore,
+            ports: _,
+            history_enabled,
+            history: _,
+            newline_policy,
+            overwrite_policy,
+            socket_dir: _,
+        } = self;
+        let mut environment = IntegrationTestEnvironment::new(new_label);
+        // The entries and their modes.
+        environment.merge_plan(self);
+        environment.golden_ignore_trailing_newline = *golden_ignore_trailing_newline;
+        environment.envs = envs.clone();
+        environment.path_dirs = path_dirs.clone();
+        environment.panic_on_error = *panic_on_error;
+        environment.concurrent_timeout = *concurrent_timeout;
+        environment.bin_paths = bin_paths.clone();
+        environment.tree_ignore = tree_ignore.clone();
+        environment.history_enabled = *history_enabled;
+        environment.newline_policy = *newline_policy;
+        environment.overwrite_policy = *overwrite_policy;
+        environment
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn set_env<K, V>(&mut self, key: K, value: V)
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.envs
+            .insert(key.as_ref().to_string(), value.as_ref().to_string());
+    }
+
+    pub fn env_vars(&self) -> &HashMap<String, String> {
+        &self.envs
+    }
+
+    // Sorted by path, like the order used by `setup()`.
+    pub fn entries(&self) -> impl Iterator<Item = (&Path, EntryKind)> {
+        self.sorted_entries()
+            .into_iter()
+            .map(|(path, entry)| (path.as_path(), entry.kind()))
+    }
+
+    // Paths are normalized like the staged ones, a path escaping the root is never staged.
+    pub fn has_entry<P: AsRef<Path>>(&self, path: P) -> bool {
+        normalize(path.as_ref()).is_ok_and(|path| self.entries.contains_key(&path))
+    }
+
+    pub fn remove_entry<P: AsRef<Path>>(&mut self, path: P) -> bool {
+        let path = match normalize(path.as_ref()) {
+            Ok(path) => path,
+            Err(_) => return false,
+        };
+        self.modes.remove(&path);
+        self.entries.remove(&path).is_some()
+    }
+
+    pub fn planned_entries(&self) -> Vec<(PathBuf, bool)> {
+        self.sorted_entries()
+            .into_iter()
+            .map(|(path, entry)| (path.clone(), entry.is_dir()))
+            .collect()
+    }
+
+    pub(crate) fn sorted_entries(&self) -> Vec<(&PathBuf, &Entry)> {
+        let mut entries: Vec<(&PathBuf, &Entry)> = self.entries.iter().collect();
+        entries.sort_by_key(|(path, _)| *path);
+        entries
     }
 
     pub fn setup(&self) {
-        for (path, content) in self.entries.iter() {
-            let path = self.tmp_dir.path().join(path);
-            if let Some(content) = content {
-                if let Some(path) = path.parent() {
-                    create_all(path, false)
-                        .expect(format!("fail to create directory {:?}", path).as_str())
+        self.setup_with(&|_| {});
+    }
+
+    // The operations are returned in the order they ran instead of being printed among
+    // the test output.
+    pub fn setup_verbose(&self) -> Vec<PlannedOp> {
+        let ops = RefCell::new(vec![]);
+        self.setup_with(&|op| ops.borrow_mut().push(op.clone()));
+        ops.into_inner()
+    }
+
+    fn setup_with(&self, on_op: &dyn Fn(&PlannedOp)) {
+        if let Err(error) = check_conflicts(&self.sorted_entries()) {
+            self.report_error(error);
+            return;
+        }
+        let plan = self.plan();
+        self.execute_plan(&plan, on_op);
+        if let Some(hook) = &self.post_setup_hook {
+            hook(self.tmp_dir.path());
+        }
+    }
+
+    // The plan lists the operations kind by kind, the files are written in parallel and
+    // reported once they all are. `on_op` is called once an operation is done.
+    fn execute_plan(&self, plan: &[PlannedOp], on_op: &dyn Fn(&PlannedOp)) {
+        let root = self.tmp_dir.path();
+        // Generators are not thread safe, their content is produced here before any write.
+        let generated: HashMap<&Path, Ref<'_, String>> = plan
+            .iter()
+            .filter_map(|op| match (op, self.entries.get(op.path())) {
+                (PlannedOp::WriteFile { path, .. }, Some(Entry::Generated(generator))) => {
+                    Some((path.as_path(), generator.content()))
                 }
-                write_all(path, content).expect("fail to create file");
-            } else {
-                create_all(&path, false)
-                    .expect(format!("fail to create directory {:?}", path).as_str())
+                _ => None,
+            })
+            .collect();
+
+        let mut dirs = vec![];
+        let mut file_ops = vec![];
+        let mut files: Vec<(PathBuf, &[u8])> = vec![];
+        let mut copies = vec![];
+        let mut links = vec![];
+        let mut fifos = vec![];
+        #[cfg(feature = "sqlite")]
+        let mut databases = vec![];
+        let mut modes = vec![];
+        for op in plan {
+            match op {
+                PlannedOp::CreateDir { path } => dirs.push((op, root.join(path))),
+                PlannedOp::WriteFile { path, .. } => {
+                    let content = match self.entries.get(path) {
+                        Some(Entry::File(content)) => content.as_bytes(),
+                        Some(Entry::Bytes(content)) => content,
+                        Some(Entry::Generated(_)) => generated[path.as_path()].as_bytes(),
+                        _ => unreachable!("write planned without content {:?}", path),
+                    };
+                    file_ops.push(op);
+                    files.push((root.join(path), content));
+                }
+                PlannedOp::CopyFile { path, source, mode } => {
+                    copies.push((op, root.join(path), source, *mode))
+                }
+                PlannedOp::HardLink { path, target } => {
+                    links.push((op, root.join(path), root.join(target)))
+                }
+                PlannedOp::CreateFifo { path } => fifos.push((op, root.join(path))),
+                #[cfg(feature = "sqlite")]
+                PlannedOp::CreateSqliteDb { path } => match self.entries.get(path) {
+                    Some(Entry::Sqlite(sql)) => databases.push((op, root.join(path), sql)),
+                    _ => unreachable!("database planned without sql {:?}", path),
+                },
+                PlannedOp::SetMode { path, mode } => modes.push((op, root.join(path), *mode)),
+            }
+        }
+
+        let mut errors = vec![];
+        for (op, path) in dirs {
+            if let Err(error) = create_all(&path, false) {
+                errors.push(format!("fail to create directory {:?}: {}", path, error));
+            }
+            on_op(op);
+        }
+        errors.extend(write_files(&files));
+        file_ops.into_iter().for_each(on_op);
+        for (op, path, source, mode) in copies {
+            if let Err(error) = copy_entry(source, &path, mode) {
+                errors.push(format!(
+                    "fail to copy {:?} to {:?}: {}",
+                    source, path, error
+                ));
+            }
+            on_op(op);
+        }
+        for (op, path, target) in links {
+            if let Err(error) = hard_link(&target, &path) {
+                errors.push(format!(
+                    "fail to create hard link {:?} to {:?}: {}",
+                    path, target, error
+                ));
+            }
+            on_op(op);
+        }
+        for (op, path) in fifos {
+            if let Err(error) = fifo::create_fifo(&path) {
+                errors.push(format!("fail to create fifo {:?}: {}", path, error));
+            }
+            on_op(op);
+        }
+        #[cfg(feature = "sqlite")]
+        for (op, path, sql) in databases {
+            if let Err(error) = sqlite::create_db(&path, sql) {
+                errors.push(error);
+            }
+            on_op(op);
+        }
+        #[cfg(unix)]
+        for (op, path, mode) in modes {
+            use std::fs::{set_permissions, Permissions};
+            use std::os::unix::fs::PermissionsExt;
+            if let Err(error) = set_permissions(&path, Permissions::from_mode(mode)) {
+                errors.push(format!("fail to set permissions {:?}: {}", path, error));
             }
+            on_op(op);
         }
+        // Windows has no permission bits to set.
+        #[cfg(not(unix))]
+        modes.into_iter().for_each(|(op, _, _)| on_op(op));
+        self.report_errors(errors);
     }
 
+    pub(crate) fn sorted_modes(&self) -> Vec<(&PathBuf, &u32)> {
+        let mut modes: Vec<(&PathBuf, &u32)> = self.modes.iter().collect();
+        modes.sort();
+        modes
+    }
+
+    #[cfg(unix)]
     pub fn set_exec_permission<P: AsRef<Path>>(&self, file: P) -> io::Result<()> {
-        let file = self.tmp_dir.path().join(file.as_ref());
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        let file = checked_path(file.as_ref())?;
+        self.detach(&file)?;
+        let file = self.tmp_dir.path().join(file);
         let permissions = Permissions::from_mode(0o755);
         set_permissions(file, permissions)?;
         Ok(())
     }
 
+    // Directories keep their own mode, only the files beneath `root` are changed.
+    #[cfg(unix)]
+    pub fn set_permissions_recursive<P: AsRef<Path>>(&self, root: P, mode: u32) -> io::Result<()> {
+        use std::fs::{set_permissions, Permissions};
+        use std::os::unix::fs::PermissionsExt;
+        let root = checked_path(root.as_ref())?;
+        for dir_entry in WalkDir::new(self.tmp_dir.path().join(&root)) {
+            let dir_entry = dir_entry?;
+            if !dir_entry.file_type().is_file() {
+                continue;
+            }
+            let relative = dir_entry
+                .path()
+                .strip_prefix(self.tmp_dir.path())
+                .expect("directory entry outside of the environment");
+            self.detach(relative)?;
+            set_permissions(dir_entry.path(), Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    // Windows has no executable bit, the file only has to exist there.
+    pub fn make_executable<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        #[cfg(unix)]
+        {
+            self.set_exec_permission(path)
+        }
+        #[cfg(not(unix))]
+        {
+            self.tmp_dir
+                .path()
+                .join(checked_path(path.as_ref())?)
+                .metadata()
+                .map(|_| ())
+        }
+    }
+
     pub fn set_update_file_time<P: AsRef<Path>>(&self, file: P) -> io::Result<()> {
-        let content = self.read_file(file.as_ref());
-        let file = self.tmp_dir.path().join(file.as_ref());
-        let mut file = OpenOptions::new().write(true).truncate(true).open(file)?;
-        write!(file, "{}", content)?;
+        // Raw bytes, the newline policy must not leak into the file.
+        let file = checked_path(file.as_ref())?;
+        let path = self.tmp_dir.path().join(&file);
+        let content = std::fs::read(&path)?;
+        self.detach(&file)?;
+        let mut file = OpenOptions::new().write(true).truncate(true).open(path)?;
+        file.write_all(&content)?;
         Ok(())
     }
 
     pub fn tree(&self) -> Vec<PathBuf> {
-        let mut tree: Vec<PathBuf> = WalkDir::new(self.tmp_dir.path())
+        let mut tree: Vec<PathBuf> = self.tree_iter().collect();
+        tree.sort();
+        tree
+    }
+
+    // The root is at depth 0, its children at depth 1.
+    pub fn tree_depth(&self, max_depth: usize) -> Vec<PathBuf> {
+        let mut tree: Vec<PathBuf> = self.walk_tree(max_depth, &self.tree_ignore).collect();
+        tree.sort();
+        tree
+    }
+
+    // Paths come in directory-walk order, use `tree()` when a sorted list is needed.
+    pub fn tree_iter(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.walk_tree(usize::MAX, &self.tree_ignore)
+    }
+
+    // Like `tree()` without the ignore list, which only applies to the listings.
+    pub(crate) fn full_tree(&self) -> Vec<PathBuf> {
+        let mut tree: Vec<PathBuf> = self.walk_tree(usize::MAX, &[]).collect();
+        tree.sort();
+        tree
+    }
+
+    fn walk_tree<'a>(
+        &'a self,
+        max_depth: usize,
+        ignore: &'a [ignore::IgnorePattern],
+    ) -> impl Iterator<Item = PathBuf> + 'a {
+        WalkDir::new(self.tmp_dir.path())
+            .max_depth(max_depth)
             .into_iter()
-            .filter_map(|dir_entry| {
+            .filter_entry(move |dir_entry| {
+                let relative = dir_entry
+                    .path()
+                    .strip_prefix(self.tmp_dir.path())
+                    .unwrap_or(dir_entry.path());
+                !ignore::is_ignored(ignore, relative)
+            })
+            .filter_map(move |dir_entry| {
                 if let Ok(dir_entry) = dir_entry {
                     if let Ok(dir_entry) = dir_entry.path().strip_prefix(self.tmp_dir.path()) {
                         return Some(dir_entry.to_path_buf());
@@ -112,71 +802,399 @@ impl IntegrationTestEnvironment {
                 }
                 None
             })
+    }
+
+    pub fn tree_files(&self) -> Vec<PathBuf> {
+        self.tree()
+            .into_iter()
+            .filter(|path| !self.tmp_dir.path().join(path).is_dir())
+            .collect()
+    }
+
+    pub fn tree_count(&self) -> usize {
+        self.tree_iter().count()
+    }
+
+    pub fn read_dir<P: AsRef<Path>>(&self, path: P) -> Vec<PathBuf> {
+        self.read_dir_depth(path.as_ref(), 1)
+    }
+
+    pub fn read_dir_recursive<P: AsRef<Path>>(&self, path: P) -> Vec<PathBuf> {
+        self.read_dir_depth(path.as_ref(), usize::MAX)
+    }
+
+    fn read_dir_depth(&self, path: &Path, max_depth: usize) -> Vec<PathBuf> {
+        let dir = match normalize(path) {
+            Ok(relative) => self.tmp_dir.path().join(relative),
+            Err(reason) => {
+                self.report_error(format!("fail to read directory {:?}: {}", path, reason));
+                return vec![];
+            }
+        };
+        if !dir.exists() {
+            self.report_error(format!(
+                "fail to read directory {:?}: it does not exist",
+                dir
+            ));
+            return vec![];
+        }
+        if !dir.is_dir() {
+            self.report_error(format!("fail to read directory {:?}: it is a file", dir));
+            return vec![];
+        }
+        let children: Result<Vec<PathBuf>, _> = WalkDir::new(&dir)
+            .min_depth(1)
+            .max_depth(max_depth)
+            .into_iter()
+            .map(|dir_entry| {
+                dir_entry.map(|dir_entry| {
+                    dir_entry
+                        .path()
+                        .strip_prefix(self.tmp_dir.path())
+                        .expect("directory entry outside of the environment")
+                        .to_path_buf()
+                })
+            })
             .collect();
-        tree.sort();
-        tree
+        match children {
+            Ok(mut children) => {
+                children.sort();
+                children
+            }
+            Err(_) => {
+                self.report_error(format!("fail to read directory {:?}", dir));
+                vec![]
+            }
+        }
     }
 
     pub fn command<C>(&self, crate_name: C) -> io::Result<Command>
     where
         C: AsRef<str>,
     {
-        let mut command = Command::cargo_bin(crate_name).unwrap();
-        command.current_dir(&self.tmp_dir.path());
-        let command = (self.cfg_command_callback)(self.path()?.clone().to_path_buf(), command);
+        match self.try_command(crate_name) {
+            Ok(command) => Ok(command),
+            Err(CargoBinError::Io(error)) => Err(error),
+            Err(error) => panic!("{}", error),
+        }
+    }
+
+    pub fn command_args<C>(&self, crate_name: C, args: &[&str]) -> Command
+    where
+        C: AsRef<str>,
+    {
+        self.command_with_args(crate_name, args)
+    }
+
+    pub fn try_command<C>(&self, crate_name: C) -> Result<Command, CargoBinError>
+    where
+        C: AsRef<str>,
+    {
+        let crate_name = crate_name.as_ref();
+        let command = Command::from_std(self.std_command(crate_name)?);
+        let command = (self.cfg_command_callback)(crate_name, &self.path()?, command)?;
+        Ok(command)
+    }
+
+    // The working directory, the environment variables, the search path and the command
+    // callback, as applied by `command()`.
+    pub fn configure_command(&self, crate_name: &str, mut cmd: Command) -> Command {
+        cmd.current_dir(self.canonical_path());
+        cmd.envs(&self.envs);
+        if let Some(path) = self.command_path() {
+            cmd.env("PATH", path);
+        }
+        (self.cfg_command_callback)(crate_name, &self.canonical_path(), cmd).unwrap_or_else(
+            |error| {
+                panic!(
+                    "fail to configure the command of {:?}: {}",
+                    crate_name, error
+                )
+            },
+        )
+    }
+
+    // Resolved once per crate name, `cargo_bin` probes the target directory on every call.
+    pub fn bin_path(&self, crate_name: &str) -> Result<PathBuf, CargoBinError> {
+        if let Some(path) = self.bin_paths.borrow().get(crate_name) {
+            return Ok(path.clone());
+        }
+        let command = std::process::Command::cargo_bin(crate_name).map_err(|cause| {
+            CargoBinError::NotFound {
+                crate_name: crate_name.to_string(),
+                cause,
+            }
+        })?;
+        let path = PathBuf::from(command.get_program());
+        self.bin_paths
+            .borrow_mut()
+            .insert(crate_name.to_string(), path.clone());
+        Ok(path)
+    }
+
+    pub fn clear_command_cache(&self) {
+        self.bin_paths.borrow_mut().clear();
+    }
+
+    pub(crate) fn std_command(
+        &self,
+        crate_name: &str,
+    ) -> Result<std::process::Command, CargoBinError> {
+        let mut command = std::process::Command::new(self.bin_path(crate_name)?);
+        // The CLI under test sees the same root as `canonical_path()`, even when the
+        // temporary directory sits behind a symbolic link (`/var` on macOS).
+        command.current_dir(self.canonical_path());
+        command.envs(&self.envs);
+        if let Some(path) = self.command_path() {
+            command.env("PATH", path);
+        }
         Ok(command)
     }
 
+    // `assert_cmd::Command` cannot be spawned, the command built by `try_command()` is
+    // copied back. Only what it exposes survives: no stdin, timeout or `env_clear`.
+    pub(crate) fn spawnable_command(
+        &self,
+        crate_name: &str,
+    ) -> Result<std::process::Command, CargoBinError> {
+        let command = self.try_command(crate_name)?;
+        let mut spawnable = std::process::Command::new(command.get_program());
+        spawnable.args(command.get_args());
+        for (key, value) in command.get_envs() {
+            match value {
+                Some(value) => spawnable.env(key, value),
+                None => spawnable.env_remove(key),
+            };
+        }
+        if let Some(dir) = command.get_current_dir() {
+            spawnable.current_dir(dir);
+        }
+        Ok(spawnable)
+    }
+
     pub fn path(&self) -> io::Result<PathBuf> {
         self.tmp_dir.path().canonicalize()
     }
+
+    pub fn canonical_path(&self) -> PathBuf {
+        self.path()
+            .unwrap_or_else(|_| panic!("fail to canonicalize {:?}", self.tmp_dir.path()))
+    }
+
+    // Both spellings of the root are replaced, the longest first since the raw root can
+    // be a suffix of the canonical one (`/var/...` and `/private/var/...`).
+    pub fn strip_root(&self, value: &str) -> String {
+        let raw = self.tmp_dir.path().display().to_string();
+        let canonical = self.canonical_path().display().to_string();
+        replace_roots(value, &raw, &canonical)
+    }
+
+    pub fn temp_dir(&self) -> &TempDir {
+        &self.tmp_dir
+    }
+
+    // The directory is no longer removed, deleting it is up to the caller.
+    pub fn into_path(self) -> PathBuf {
+        self.tmp_dir.into_path()
+    }
 }
 
-impl Display for IntegrationTestEnvironment {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        for e in self.tree() {
-            writeln!(f, "{}", e.to_string_lossy())?;
+pub(crate) fn permissions_mode(metadata: &Metadata) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o7777
+    }
+    #[cfg(not(unix))]
+    {
+        if metadata.permissions().readonly() {
+            0o444
+        } else {
+            0o644
         }
-        Ok(())
     }
 }
 
-#[cfg(test)]
-mod test {
-    use crate::IntegrationTestEnvironment;
-    use predicates::prelude::Predicate;
-    use predicates::str::contains;
+pub(crate) fn display_path(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
 
-    #[test]
-    fn integration_test_environment() {
-        let mut e = IntegrationTestEnvironment::new("test");
-        e.add_file("file1", "test 1");
-        e.add_file("dir/file2", "test 2");
-        e.add_dir("emptry_dir");
-        e.setup();
-        e.set_exec_permission("dir/file2").unwrap();
-        let display = e.to_string();
-        assert!(contains("file1").eval(display.as_str()));
-        assert!(contains("dir/file2").eval(display.as_str()));
-        assert!(contains("emptry_dir").eval(display.as_str()));
-        assert!(contains("test 1").eval(e.read_file("file1").as_str()));
+// Labels are free text, only a short portable subset of it names the temporary directory.
+fn tmp_dir_prefix(label: &str) -> String {
+    const MAX_CHARS: usize = 32;
+    let prefix: String = label
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_CHARS)
+        .collect();
+    if prefix.is_empty() {
+        "test".to_string()
+    } else {
+        prefix
+    }
+}
+
+pub const ROOT_TOKEN: &str = "[ROOT]";
+
+fn replace_roots(value: &str, raw: &str, canonical: &str) -> String {
+    let (first, second) = if raw.len() > canonical.len() {
+        (raw, canonical)
+    } else {
+        (canonical, raw)
+    };
+    value.replace(first, ROOT_TOKEN).replace(second, ROOT_TOKEN)
+}
+
+// Entries nested under a staged file can not be created, they are reported
+// before anything is written.
+fn check_conflicts(entries: &[(&PathBuf, &Entry)]) -> Result<(), String> {
+    let files: HashSet<&Path> = entries
+        .iter()
+        .filter(|(_, entry)| !entry.is_dir())
+        .map(|(path, _)| path.as_path())
+        .collect();
+    for (path, _) in entries {
+        if let Some(file) = path.ancestors().skip(1).find(|a| files.contains(a)) {
+            return Err(format!(
+                "fail to setup {:?}: {:?} is staged as a file, not a directory",
+                path, file
+            ));
+        }
+    }
+    Ok(())
+}
+
+const FILES_PER_THREAD: usize = 256;
+
+fn write_files(files: &[(PathBuf, &[u8])]) -> Vec<String> {
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let chunk_size = (files.len() / threads).max(FILES_PER_THREAD);
+    let write_chunk = |chunk: &'_ [(PathBuf, &[u8])]| -> Vec<String> {
+        chunk
+            .iter()
+            .filter_map(|(path, content)| {
+                write(path, content)
+                    .err()
+                    .map(|error| format!("fail to create file {:?}: {}", path, error))
+            })
+            .collect()
+    };
+    if files.len() <= chunk_size {
+        write_chunk(files)
+    } else {
+        thread::scope(|scope| {
+            let workers: Vec<_> = files
+                .chunks(chunk_size)
+                .map(|chunk| scope.spawn(move || write_chunk(chunk)))
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().expect("file writer panicked"))
+                .collect()
+        })
+    }
+}
+
+impl Display for IntegrationTestEnvironment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for e in self.tree() {
+            writeln!(f, "{}", display_path(&e))?;
+        }
+        Ok(())
+    }
+}
+
+// Only describes what has been declared, so that it is usable before `setup()`.
+impl Debug for IntegrationTestEnvironment {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let entries = self.sorted_entries();
+        f.debug_struct("IntegrationTestEnvironment")
+            .field("label", &self.label)
+            .field("path", &self.tmp_dir.path())
+            .field("entries", &entries.len())
+            .field("contents", &DebugContents(&entries))
+            .finish()
+    }
+}
+
+struct DebugContents<'a>(&'a [(&'a PathBuf, &'a Entry)]);
+
+impl Debug for DebugContents<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.0
+                    .iter()
+                    .map(|(path, entry)| (display_path(path), ContentPreview(entry))),
+            )
+            .finish()
     }
 }
 
 #[macro_export]
 macro_rules! println_output {
     ($v:ident) => {
-        println!(
-            "{}",
-            String::from_utf8($v.stderr.clone()).expect("fail to read stderr")
-        );
-        println!("---------------------------");
-        println!(
-            "{}",
-            String::from_utf8($v.stdout.clone()).expect("fail to read stdout")
-        );
-        println!("---------------------------");
-        println!("{}", $v.status);
+        println!("{}", $crate::format_output(&$v));
+    };
+}
+
+#[macro_export]
+macro_rules! debug_tree {
+    ($environment:expr) => {
+        eprint!("{}", $crate::render_debug_tree(&$environment));
+    };
+}
+
+#[macro_export]
+macro_rules! environment {
+    ($label:expr $(,)?) => {
+        $crate::IntegrationTestEnvironment::new($label)
+    };
+    ($label:expr, { $($entries:tt)* } $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut environment = $crate::IntegrationTestEnvironment::new($label);
+        $crate::environment!(@entries environment $($entries)*);
+        environment
+    }};
+    (@entries $e:ident) => {};
+    (@entries $e:ident $path:literal => dir $(, $($rest:tt)*)?) => {
+        $e.add_dir($path);
+        $crate::environment!(@entries $e $($($rest)*)?);
+    };
+    (@entries $e:ident $path:literal => exec $content:expr $(, $($rest:tt)*)?) => {
+        $e.add_executable_file($path, $content);
+        $crate::environment!(@entries $e $($($rest)*)?);
+    };
+    (@entries $e:ident $path:literal => $content:expr $(, $($rest:tt)*)?) => {
+        $e.add_file($path, $content);
+        $crate::environment!(@entries $e $($($rest)*)?);
+    };
+}
+
+#[macro_export]
+macro_rules! assert_output {
+    ($output:expr, contains $expected:expr) => {
+        $crate::assert_output(&$output, $expected, $crate::OutputMatch::Contains)
+    };
+    ($output:expr, untrimmed $expected:expr) => {
+        $crate::assert_output(&$output, $expected, $crate::OutputMatch::ExactUntrimmed)
+    };
+    ($output:expr, matches $pred:expr) => {
+        $crate::assert_output_matches(&$output, $pred)
+    };
+    ($output:expr, $expected:expr) => {
+        $crate::assert_output(&$output, $expected, $crate::OutputMatch::Exact)
     };
 }
 
@@ -185,7 +1203,7 @@ macro_rules! println_result_output {
     ($v:ident) => {
         match $v {
             Ok(output) => {
-                println_output!(output);
+                $crate::println_output!(output);
             }
             Err(outputError) => {
                 println!("output error !!");
@@ -194,3 +1212,752 @@ macro_rules! println_result_output {
         }
     };
 }
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        CargoBinError, EntryKind, IntegrationTestEnvironment, NewlinePolicy, OverwritePolicy,
+        PathMode,
+    };
+    use predicates::prelude::Predicate;
+    use predicates::str::contains;
+    use std::cell::Cell;
+    use std::fs::{read_to_string, write};
+    use std::io;
+    use std::path::{Path, PathBuf};
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    #[test]
+    fn integration_test_environment() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("file1", "test 1");
+        e.add_file("dir/file2", "test 2");
+        e.add_dir("emptry_dir");
+        e.setup();
+        #[cfg(unix)]
+        e.set_exec_permission("dir/file2").unwrap();
+        let display = e.to_string();
+        assert!(contains("file1").eval(display.as_str()));
+        assert!(contains("dir/file2").eval(display.as_str()));
+        assert!(contains("emptry_dir").eval(display.as_str()));
+        assert!(contains("test 1").eval(e.read_file("file1").as_str()));
+    }
+
+    #[test]
+    fn debug_format() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("config.toml", "key = \"value\"\n");
+        e.add_dir("data");
+        e.add_file("large.txt", "a".repeat(100));
+        e.add_file_with("generated", || unreachable!());
+        assert_eq!(
+            e.strip_root(&format!("{:?}", e)),
+            "IntegrationTestEnvironment { label: \"test\", path: \"[ROOT]\", entries: 4, contents: \
+             {\"config.toml\": \"key = \\\"value\\\"\\n\", \"data\": <dir>, \"generated\": <generated>, \
+             \"large.txt\": \"".to_string()
+                + &"a".repeat(80)
+                + "\"\u{2026} (100 bytes)} }"
+        );
+        assert!(!e.path().unwrap().join("config.toml").exists());
+    }
+
+    #[test]
+    fn try_new() {
+        let label = "absurd label-with_ünïcödé.and.dots".repeat(3);
+        let e = IntegrationTestEnvironment::try_new(&label).unwrap();
+        assert!(e.path().unwrap().is_dir());
+    }
+
+    #[test]
+    fn read_dir() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("output/b.txt", "");
+        e.add_file("output/a/nested.txt", "");
+        e.add_file("other.txt", "");
+        e.setup();
+        assert_eq!(
+            e.read_dir("output"),
+            vec![PathBuf::from("output/a"), PathBuf::from("output/b.txt")]
+        );
+        assert_eq!(
+            e.read_dir_recursive("output"),
+            vec![
+                PathBuf::from("output/a"),
+                PathBuf::from("output/a/nested.txt"),
+                PathBuf::from("output/b.txt")
+            ]
+        );
+        assert_eq!(
+            e.read_dir("output/a"),
+            vec![PathBuf::from("output/a/nested.txt")]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "missing\": it does not exist")]
+    fn read_dir_missing() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.read_dir("missing");
+    }
+
+    #[test]
+    #[should_panic(expected = "file.txt\": it is a file")]
+    fn read_dir_file() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("file.txt", "");
+        e.setup();
+        e.read_dir("file.txt");
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to read file")]
+    fn panic_on_error() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.read_file("missing");
+    }
+
+    #[test]
+    fn record_errors() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.set_panic_on_error(false);
+        assert_eq!(e.read_file("missing"), "");
+        assert_eq!(e.file_size("missing"), 0);
+        let errors = e.take_errors();
+        assert_eq!(errors.len(), 2);
+        assert!(errors[0].starts_with("fail to read file "));
+        assert!(errors[0].ends_with("missing\""));
+        assert!(errors[1].starts_with("fail to read metadata "));
+        assert!(e.take_errors().is_empty());
+
+        e.add_file("a", "file");
+        e.add_file("a/b", "nested");
+        e.setup();
+        assert_eq!(
+            e.take_errors(),
+            vec!["fail to setup \"a/b\": \"a\" is staged as a file, not a directory"]
+        );
+    }
+
+    #[test]
+    fn file_size() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("empty.log", "");
+        e.add_file("text", "four");
+        e.setup();
+        std::fs::write(e.path().unwrap().join("binary"), [0xff, 0x00, 0xfe]).unwrap();
+        assert_eq!(e.file_size("text"), 4);
+        assert_eq!(e.file_size("binary"), 3);
+        assert!(e.file_is_empty("empty.log"));
+        assert!(!e.file_is_empty("binary"));
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to read metadata")]
+    fn file_size_missing() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.file_size("missing");
+    }
+
+    #[test]
+    fn add_empty_file() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_empty_file("dir/placeholder");
+        assert_eq!(e.dry_run()[1], "write file dir/placeholder (0 bytes)");
+        e.setup();
+        assert!(e.file_is_empty("dir/placeholder"));
+    }
+
+    #[test]
+    fn touch() {
+        use std::time::SystemTime;
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("existing", "content");
+        e.setup();
+        let path = e.path().unwrap().join("existing");
+        let past = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(past)
+            .unwrap();
+
+        e.touch("existing").unwrap();
+        e.touch("new/dir/file").unwrap();
+        assert_eq!(e.read_file("existing"), "content");
+        assert!(path.metadata().unwrap().modified().unwrap() > past);
+        assert!(e.file_is_empty("new/dir/file"));
+    }
+
+    #[test]
+    fn write_file() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("config.toml", "key = 1");
+        e.setup();
+        e.write_file("config.toml", "key = 2").unwrap();
+        e.write_file("new/dir/file", "created").unwrap();
+        assert_eq!(e.read_file("config.toml"), "key = 2");
+        assert_eq!(e.read_file("new/dir/file"), "created");
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn set_permissions_recursive() {
+        use std::os::unix::fs::PermissionsExt;
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("bin/first.sh", "echo first");
+        e.add_file("bin/nested/second.sh", "echo second");
+        e.add_file("other.sh", "echo other");
+        e.setup();
+        e.set_permissions_recursive("bin", 0o750).unwrap();
+        let mode = |path: &str| {
+            let path = e.temp_dir().path().join(path);
+            path.metadata().unwrap().permissions().mode() & 0o777
+        };
+        assert_eq!(mode("bin/first.sh"), 0o750);
+        assert_eq!(mode("bin/nested/second.sh"), 0o750);
+        assert_ne!(mode("other.sh"), 0o750);
+        assert!(e.set_permissions_recursive("missing", 0o750).is_err());
+    }
+
+    #[test]
+    fn setup_error_names_path() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        write(e.temp_dir().path().join("blocker"), "not a directory").unwrap();
+        e.add_file("blocker/inner", "");
+        e.set_panic_on_error(false);
+        e.setup();
+        let errors = e.take_errors();
+        let directory = e.temp_dir().path().join("blocker");
+        assert!(errors[0].starts_with(&format!("fail to create directory {:?}: ", directory)));
+        let file = directory.join("inner");
+        assert!(errors[1].starts_with(&format!("fail to create file {:?}: ", file)));
+    }
+
+    #[test]
+    fn clone_plan() {
+        let mut base = IntegrationTestEnvironment::new("base");
+        base.add_file("config.toml", "key = 1");
+        base.add_executable_file("bin/run.sh", "echo");
+        base.add_dir("empty");
+        base.set_env("MODE", "test");
+        base.set_path_dir("bin", PathMode::Replace);
+        base.set_golden_ignore_trailing_newline(false);
+        base.set_panic_on_error(false);
+        base.set_concurrent_timeout(Duration::from_secs(5));
+        base.bin_paths
+            .borrow_mut()
+            .insert("tool".to_string(), PathBuf::from("/bin/tool"));
+        base.set_tree_ignore(vec!["*.lock".to_string()]);
+        base.enable_history();
+        base.set_newline_policy(NewlinePolicy::NormalizeToLf);
+        base.set_overwrite_policy(OverwritePolicy::FirstWins);
+        let clone = base.clone_plan("clone");
+        assert_eq!(clone.label(), "clone");
+        assert_eq!(clone.dry_run(), base.dry_run());
+        assert_eq!(clone.envs, base.envs);
+        assert_eq!(clone.path_dirs, base.path_dirs);
+        assert!(!clone.golden_ignore_trailing_newline);
+        assert!(!clone.panic_on_error);
+        assert_eq!(clone.concurrent_timeout, Duration::from_secs(5));
+        assert_eq!(clone.bin_paths, base.bin_paths);
+        assert_eq!(clone.tree_ignore.len(), 1);
+        assert!(clone.history_enabled);
+        assert_eq!(clone.newline_policy, NewlinePolicy::NormalizeToLf);
+        assert_eq!(clone.overwrite_policy, OverwritePolicy::FirstWins);
+
+        base.setup();
+        clone.setup();
+        assert_ne!(base.path().unwrap(), clone.path().unwrap());
+        assert_eq!(clone.tree(), base.tree());
+        assert_eq!(clone.read_file("config.toml"), "key = 1");
+    }
+
+    #[test]
+    fn post_setup_hook() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("staged", "");
+        e.set_post_setup_hook(|root| {
+            assert!(root.join("staged").exists());
+            std::fs::write(root.join("from_hook"), "custom").unwrap();
+        });
+        e.setup();
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("from_hook"),
+                PathBuf::from("staged")
+            ]
+        );
+    }
+
+    #[test]
+    fn into_path() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("kept", "still here");
+        e.setup();
+        let path = e.into_path();
+        assert_eq!(read_to_string(path.join("kept")).unwrap(), "still here");
+        std::fs::remove_dir_all(path).unwrap();
+    }
+
+    #[test]
+    fn replace_roots() {
+        let raw = "/var/folders/xy/T/test.abc";
+        let canonical = "/private/var/folders/xy/T/test.abc";
+        assert_eq!(
+            super::replace_roots(
+                "cwd: /private/var/folders/xy/T/test.abc/out, raw: /var/folders/xy/T/test.abc",
+                raw,
+                canonical
+            ),
+            "cwd: [ROOT]/out, raw: [ROOT]"
+        );
+        assert_eq!(
+            super::replace_roots("in /tmp/test.abc/a", "/tmp/test.abc", "/tmp/test.abc"),
+            "in [ROOT]/a"
+        );
+    }
+
+    #[test]
+    fn strip_root() {
+        let e = IntegrationTestEnvironment::new("test");
+        let output = format!(
+            "{} {}",
+            e.canonical_path().join("a").display(),
+            e.temp_dir().path().display()
+        );
+        assert_eq!(
+            e.strip_root(&output),
+            format!("{} {}", Path::new("[ROOT]").join("a").display(), "[ROOT]")
+        );
+    }
+
+    #[test]
+    fn environment_macro() {
+        let content = String::from("from a variable");
+        let e = crate::environment!("test", {
+            "src/main.rs" => "fn main() {}",
+            "data/" => dir,
+            "run.sh" => exec "echo hi",
+            "variable" => &content,
+        });
+        assert_eq!(
+            e.dry_run(),
+            vec![
+                "create dir data",
+                "create dir src",
+                "write file run.sh (7 bytes)",
+                "write file src/main.rs (12 bytes)",
+                "write file variable (15 bytes)",
+                "set mode run.sh 755",
+            ]
+        );
+
+        let e = crate::environment!("test", { "a" => "a", "b" => dir });
+        assert_eq!(
+            e.planned_entries(),
+            vec![(PathBuf::from("a"), false), (PathBuf::from("b"), true)]
+        );
+        assert!(crate::environment!("test", {}).is_empty());
+        assert!(crate::environment!("test").is_empty());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn add_executable_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_executable_file("bin/run.sh", "#!/bin/sh\n");
+        e.setup();
+        let metadata = e.path().unwrap().join("bin/run.sh").metadata().unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o755);
+    }
+
+    #[test]
+    fn add_dir_intermediate() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("a/file", "staged");
+        e.add_dir("a/b/c");
+        assert_eq!(
+            e.planned_entries(),
+            vec![
+                (PathBuf::from("a"), true),
+                (PathBuf::from("a/b"), true),
+                (PathBuf::from("a/b/c"), true),
+                (PathBuf::from("a/file"), false),
+            ]
+        );
+        e.add_file("x", "file");
+        e.add_dir("x/y");
+        assert_eq!(e.planned_entries()[4], (PathBuf::from("x"), false));
+    }
+
+    #[test]
+    fn remove_entry() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("Cargo.toml", "[package]");
+        e.add_file("Cargo.lock", "# lockfile");
+        e.add_dir("src");
+        e.add_hardlink("link", "Cargo.toml");
+        assert_eq!(
+            e.entries().collect::<Vec<_>>(),
+            vec![
+                (Path::new("Cargo.lock"), EntryKind::File),
+                (Path::new("Cargo.toml"), EntryKind::File),
+                (Path::new("link"), EntryKind::Hardlink),
+                (Path::new("src"), EntryKind::Dir),
+            ]
+        );
+        assert!(e.has_entry("Cargo.lock"));
+        assert!(e.remove_entry("Cargo.lock"));
+        assert!(!e.remove_entry("Cargo.lock"));
+        assert!(!e.has_entry("Cargo.lock"));
+        e.setup();
+        assert!(!e.file_exists("Cargo.lock"));
+        assert!(e.file_exists("Cargo.toml"));
+    }
+
+    #[test]
+    fn remove_entry_normalized() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_executable_file("./bin/run.sh", "echo run");
+        assert!(e.has_entry("./bin/run.sh"));
+        assert!(e.has_entry("bin/./run.sh"));
+        assert!(!e.has_entry("../bin/run.sh"));
+        assert!(!e.remove_entry("../bin/run.sh"));
+        assert!(e.remove_entry("./bin/run.sh"));
+        assert!(!e.has_entry("bin/run.sh"));
+        assert!(e.dry_run().is_empty());
+    }
+
+    #[test]
+    fn overwrite_policy() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("config.toml", "first");
+        e.add_file("config.toml", "last");
+        assert_eq!(e.dry_run(), vec!["write file config.toml (4 bytes)"]);
+
+        e.set_overwrite_policy(OverwritePolicy::FirstWins);
+        e.add_file("config.toml", "ignored");
+        e.add_dir("config.toml");
+        assert_eq!(e.dry_run(), vec!["write file config.toml (4 bytes)"]);
+
+        e.set_overwrite_policy(OverwritePolicy::Panic);
+        e.add_dir("a/b");
+        e.add_dir("a");
+        e.add_file("other", "");
+        assert_eq!(e.len(), 4);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "fail to add \"config.toml\": already added as file \"key = 1\", added again as file \"key = 2\""
+    )]
+    fn overwrite_policy_panic() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.set_overwrite_policy(OverwritePolicy::Panic);
+        e.add_file("config.toml", "key = 1");
+        e.add_file("config.toml", "key = 2");
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "fail to add \"data\": already added as file \"01234567890123456789012345678901234567890123456789012345678901234567890123456789\"\u{2026} (100 bytes), added again as directory"
+    )]
+    fn overwrite_policy_panic_file_then_dir() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.set_overwrite_policy(OverwritePolicy::Panic);
+        e.add_file("data", "0123456789".repeat(10));
+        e.add_dir("data");
+    }
+
+    #[test]
+    fn merge_plan() {
+        let mut config = IntegrationTestEnvironment::new("config");
+        config.add_file("config.toml", "key = 1");
+        config.add_file("shared", "from config");
+        let mut data = IntegrationTestEnvironment::new("data");
+        data.add_dir("data");
+        data.add_file("shared", "from data");
+        data.add_file_with("data/generated", || "generated".to_string());
+
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.merge_plan(&config);
+        e.merge_plan(&data);
+        e.setup();
+        assert_eq!(
+            e.tree(),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("config.toml"),
+                PathBuf::from("data"),
+                PathBuf::from("data/generated"),
+                PathBuf::from("shared"),
+            ]
+        );
+        assert_eq!(e.read_file("shared"), "from data");
+        assert_eq!(e.read_file("data/generated"), "generated");
+        assert_eq!(config.tree(), vec![PathBuf::from("")]);
+    }
+
+    #[test]
+    fn read_lines() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("crlf", "name\r\nversion = 1\r\n");
+        e.add_file("no_trailing_newline", "a\n\nb");
+        e.add_file("empty", "");
+        e.setup();
+        assert_eq!(e.read_lines("crlf"), vec!["name", "version = 1"]);
+        assert_eq!(e.read_line("crlf", 1), "version = 1");
+        assert_eq!(e.read_lines("no_trailing_newline"), vec!["a", "", "b"]);
+        assert_eq!(e.read_line("no_trailing_newline", 2), "b");
+        assert!(e.read_lines("empty").is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to read line 2 of \"lockfile\": the file has 2 lines")]
+    fn read_line_out_of_range() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("lockfile", "name\nversion\n");
+        e.setup();
+        e.read_line("lockfile", 2);
+    }
+
+    #[test]
+    fn delete_file() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("dir/file", "content");
+        e.setup();
+        e.delete_file("dir/file").unwrap();
+        assert_eq!(e.tree(), vec![PathBuf::from(""), PathBuf::from("dir")]);
+        let error = e.delete_file("dir/file").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert_eq!(e.planned_entries().len(), 1);
+    }
+
+    #[test]
+    fn temp_dir() {
+        let e = IntegrationTestEnvironment::new("test");
+        assert_eq!(
+            e.temp_dir().path().canonicalize().unwrap(),
+            e.path().unwrap()
+        );
+        assert!(e.temp_dir().path().is_dir());
+    }
+
+    #[test]
+    fn label_with_spaces() {
+        let e = IntegrationTestEnvironment::new("reads config/with spaces: ✓");
+        assert_eq!(e.label(), "reads config/with spaces: ✓");
+        let name = e.path().unwrap().file_name().unwrap().to_owned();
+        assert!(name
+            .to_str()
+            .unwrap()
+            .starts_with("reads_config_with_spaces___"));
+        assert!(e.path().unwrap().is_dir());
+
+        let e = IntegrationTestEnvironment::new_with_prefix("described label", "short");
+        assert_eq!(e.label(), "described label");
+        let name = e.path().unwrap().file_name().unwrap().to_owned();
+        assert!(name.to_str().unwrap().starts_with("short"));
+    }
+
+    #[test]
+    fn planned_entries() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("dir/file2", "test 2");
+        e.add_dir("empty_dir");
+        e.add_file("file1", "test 1");
+        assert_eq!(
+            e.planned_entries(),
+            vec![
+                (PathBuf::from("dir/file2"), false),
+                (PathBuf::from("empty_dir"), true),
+                (PathBuf::from("file1"), false),
+            ]
+        );
+        assert!(e.tree().iter().all(|p| p.as_os_str().is_empty()));
+    }
+
+    #[test]
+    #[should_panic(expected = "absolute paths are rejected")]
+    fn read_file_absolute_path() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("etc/hostname", "sandbox");
+        e.setup();
+        e.read_file("/etc/hostname");
+    }
+
+    #[test]
+    fn len() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        assert!(e.is_empty());
+        assert_eq!(e.len(), 0);
+        e.add_file("file1", "test 1");
+        e.add_file("dir/file2", "test 2");
+        e.add_dir("empty_dir");
+        assert!(!e.is_empty());
+        assert_eq!(e.len(), 3);
+        e.add_file("file1", "test 1 bis");
+        assert_eq!(e.len(), 3);
+    }
+
+    #[test]
+    fn env_vars() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.set_env("FIRST", "1");
+        e.set_env("SECOND", "2");
+        e.set_env("FIRST", "one");
+        let mut vars: Vec<(&String, &String)> = e.env_vars().iter().collect();
+        vars.sort();
+        assert_eq!(
+            vars,
+            vec![
+                (&"FIRST".to_string(), &"one".to_string()),
+                (&"SECOND".to_string(), &"2".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn add_file_with() {
+        let calls = Rc::new(Cell::new(0));
+        let mut e = IntegrationTestEnvironment::new("test");
+        let generator_calls = Rc::clone(&calls);
+        e.add_file_with("data.csv", move || {
+            generator_calls.set(generator_calls.get() + 1);
+            (0..3).map(|i| format!("{},{}\n", i, i * i)).collect()
+        });
+        assert_eq!(calls.get(), 0);
+        e.setup();
+        e.write_file("data.csv", "modified").unwrap();
+        e.setup();
+        #[cfg(feature = "serde")]
+        e.to_manifest();
+        assert_eq!(calls.get(), 1);
+        assert_eq!(e.read_file("data.csv"), "0,0\n1,1\n2,4\n");
+    }
+
+    #[test]
+    fn dry_run() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("a/b/c.txt", "fourteen bytes");
+        e.add_file("a/b/d.txt", "");
+        e.add_dir("e");
+        e.add_file_with("f", String::new);
+        assert_eq!(
+            e.dry_run(),
+            vec![
+                "create dir a/b",
+                "create dir e",
+                "write file a/b/c.txt (14 bytes)",
+                "write file a/b/d.txt (0 bytes)",
+                "write file f (generated content)",
+            ]
+        );
+        assert_eq!(e.tree(), vec![PathBuf::from("")]);
+    }
+
+    #[test]
+    fn setup_many_entries() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        for i in 0..3_000 {
+            e.add_file(format!("dir_{}/file_{}", i % 30, i), i.to_string());
+        }
+        e.add_file_with("generated", || "generated".to_string());
+        e.setup();
+        assert_eq!(e.tree_count(), 3_000 + 30 + 2);
+        for i in (0..3_000).step_by(97) {
+            assert_eq!(
+                e.read_file(format!("dir_{}/file_{}", i % 30, i)),
+                i.to_string()
+            );
+        }
+        assert_eq!(e.read_file("generated"), "generated");
+    }
+
+    #[test]
+    fn setup_conflict() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("a", "file");
+        e.add_file("a/b", "nested");
+        e.add_file("c", "file");
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| e.setup()));
+        let error = result.unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<String>().unwrap(),
+            "fail to setup \"a/b\": \"a\" is staged as a file, not a directory"
+        );
+        assert_eq!(e.tree(), vec![PathBuf::from("")]);
+    }
+
+    #[test]
+    fn tree_iter() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        for i in 0..10_000 {
+            e.add_file(format!("dir_{}/file_{}", i % 100, i), "");
+        }
+        e.setup();
+        assert_eq!(e.tree_iter().count(), e.tree().len());
+        assert_eq!(e.tree_count(), 10_000 + 100 + 1);
+        assert_eq!(
+            e.tree_iter().find(|path| path.ends_with("file_42")),
+            Some(PathBuf::from("dir_42/file_42"))
+        );
+    }
+
+    #[test]
+    fn tree_depth() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("a/b/c/deep.txt", "");
+        e.add_file("a/top.txt", "");
+        e.setup();
+        assert_eq!(e.tree_depth(0), vec![PathBuf::from("")]);
+        assert_eq!(
+            e.tree_depth(2),
+            vec![
+                PathBuf::from(""),
+                PathBuf::from("a"),
+                PathBuf::from("a/b"),
+                PathBuf::from("a/top.txt")
+            ]
+        );
+        assert_eq!(e.tree_depth(4), e.tree());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn add_hardlink() {
+        use std::os::unix::fs::MetadataExt;
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_hardlink("a/link", "z/target");
+        e.add_file("z/target", "content");
+        assert_eq!(
+            e.dry_run(),
+            vec![
+                "create dir z",
+                "create dir a",
+                "write file z/target (7 bytes)",
+                "hard link a/link -> z/target",
+            ]
+        );
+        e.setup();
+        let root = e.path().unwrap();
+        let link = root.join("a/link").metadata().unwrap();
+        let target = root.join("z/target").metadata().unwrap();
+        assert_eq!(link.ino(), target.ino());
+        assert_eq!(target.nlink(), 2);
+        assert_eq!(e.read_file("a/link"), "content");
+    }
+
+    #[test]
+    fn try_command_nonexistent_binary() {
+        let e = IntegrationTestEnvironment::new("test");
+        let error = e.try_command("nonexistent-binary").unwrap_err();
+        assert!(matches!(error, CargoBinError::NotFound { .. }));
+        assert!(contains("\"nonexistent-binary\"").eval(error.to_string().as_str()));
+    }
+}
@@ -0,0 +1,265 @@
+use crate::entry::Entry;
+use crate::IntegrationTestEnvironment;
+use serde::{Deserialize, Serialize};
+use std::fs::read;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManifestKind {
+    File,
+    Dir,
+    Hardlink,
+    Fifo,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+// A file holds either `content` or `base64`, a hard link holds its `target` and a
+// sqlite database the SQL creating it in `content`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: PathBuf,
+    pub kind: ManifestKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base64: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl IntegrationTestEnvironment {
+    // Generated contents are produced and copied fixtures are read, so that the manifest
+    // describes the environment on its own.
+    pub fn to_manifest(&self) -> Manifest {
+        let entries = self
+            .sorted_entries()
+            .into_iter()
+            .map(|(path, entry)| {
+                let mut manifest_entry = ManifestEntry {
+                    path: path.clone(),
+                    kind: ManifestKind::File,
+                    content: None,
+                    base64: None,
+                    target: None,
+                    mode: self.modes.get(path).copied(),
+                };
+                match entry {
+                    Entry::File(content) => manifest_entry.content = Some(content.clone()),
+                    Entry::Bytes(content) => set_bytes(&mut manifest_entry, content.clone()),
+                    Entry::Generated(generator) => {
+                        manifest_entry.content = Some(generator.content().clone())
+                    }
+                    Entry::Copy { source, .. } => {
+                        let content = read(source)
+                            .unwrap_or_else(|error| panic!("fail to read {:?}: {}", source, error));
+                        set_bytes(&mut manifest_entry, content)
+                    }
+                    Entry::Hardlink(target) => {
+                        manifest_entry.kind = ManifestKind::Hardlink;
+                        manifest_entry.target = Some(target.clone());
+                    }
+                    Entry::Fifo => manifest_entry.kind = ManifestKind::Fifo,
+                    #[cfg(feature = "sqlite")]
+                    Entry::Sqlite(sql) => {
+                        manifest_entry.kind = ManifestKind::Sqlite;
+                        manifest_entry.content = Some(sql.clone());
+                    }
+                    Entry::Dir => manifest_entry.kind = ManifestKind::Dir,
+                }
+                manifest_entry
+            })
+            .collect();
+        Manifest { entries }
+    }
+
+    pub fn from_manifest<L: AsRef<str>>(label: L, manifest: &Manifest) -> Self {
+        let mut environment = IntegrationTestEnvironment::new(label);
+        for manifest_entry in &manifest.entries {
+            let path = environment.entry_path(&manifest_entry.path);
+            let entry = match manifest_entry.kind {
+                ManifestKind::Dir => Entry::Dir,
+                ManifestKind::Fifo => Entry::Fifo,
+                #[cfg(feature = "sqlite")]
+                ManifestKind::Sqlite => {
+                    Entry::Sqlite(manifest_entry.content.clone().unwrap_or_default())
+                }
+                ManifestKind::Hardlink => match &manifest_entry.target {
+                    Some(target) => Entry::Hardlink(environment.entry_path(target)),
+                    None => panic!("fail to add {:?}: hard link without target", path),
+                },
+                ManifestKind::File => match (&manifest_entry.content, &manifest_entry.base64) {
+                    (Some(_), Some(_)) => {
+                        panic!("fail to add {:?}: both content and base64 are set", path)
+                    }
+                    (Some(content), None) => Entry::File(content.clone()),
+                    (None, Some(encoded)) => {
+                        Entry::Bytes(decode_base64(encoded).unwrap_or_else(|reason| {
+                            panic!("fail to add {:?}: invalid base64, {}", path, reason)
+                        }))
+                    }
+                    (None, None) => Entry::File(String::new()),
+                },
+            };
+            environment.stage(path.clone(), entry);
+            if let Some(mode) = manifest_entry.mode {
+                environment.modes.insert(path, mode);
+            }
+        }
+        environment
+    }
+
+    // Manifest files are JSON.
+    pub fn from_manifest_file<L, P>(label: L, path: P) -> io::Result<Self>
+    where
+        L: AsRef<str>,
+        P: AsRef<Path>,
+    {
+        let content = read(path.as_ref())?;
+        let manifest: Manifest = serde_json::from_slice(&content).map_err(|error| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{:?}: {}", path.as_ref(), error),
+            )
+        })?;
+        Ok(Self::from_manifest(label, &manifest))
+    }
+}
+
+fn set_bytes(manifest_entry: &mut ManifestEntry, content: Vec<u8>) {
+    match String::from_utf8(content) {
+        Ok(content) => manifest_entry.content = Some(content),
+        Err(error) => manifest_entry.base64 = Some(encode_base64(error.as_bytes())),
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_base64(encoded: &str) -> Result<Vec<u8>, String> {
+    let encoded = encoded.trim_end_matches('=');
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut group = 0u32;
+    for (i, character) in encoded.bytes().enumerate() {
+        let value = BASE64_ALPHABET
+            .iter()
+            .position(|byte| *byte == character)
+            .ok_or_else(|| format!("unexpected character {:?}", character as char))?;
+        group = group << 6 | value as u32;
+        if i % 4 == 3 {
+            bytes.extend_from_slice(&group.to_be_bytes()[1..]);
+            group = 0;
+        }
+    }
+    match encoded.len() % 4 {
+        0 => {}
+        2 => bytes.push((group >> 4) as u8),
+        3 => bytes.extend_from_slice(&((group >> 2) as u16).to_be_bytes()),
+        _ => return Err("truncated content".to_string()),
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{decode_base64, encode_base64};
+    use crate::{IntegrationTestEnvironment, Manifest, ManifestKind};
+    use std::fs::write;
+    use tempdir::TempDir;
+
+    #[test]
+    fn base64() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\x00\xff\xfe", "AP/+"),
+        ] {
+            assert_eq!(encode_base64(bytes), encoded);
+            assert_eq!(decode_base64(encoded).unwrap(), bytes);
+        }
+        assert!(decode_base64("Z").is_err());
+        assert!(decode_base64("Zm!v").is_err());
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let mut e = IntegrationTestEnvironment::new("original");
+        e.add_file("config.toml", "key = 1");
+        e.add_dir("data/empty");
+        e.add_executable_file("bin/run.sh", "echo run");
+        e.add_file_with("generated", || "generated".to_string());
+        let mut manifest = e.to_manifest();
+        manifest.entries.push(crate::ManifestEntry {
+            path: "data/blob.bin".into(),
+            kind: ManifestKind::File,
+            content: None,
+            base64: Some(encode_base64(b"\x00\xffbinary")),
+            target: None,
+            mode: None,
+        });
+        let json = serde_json::to_string_pretty(&manifest).unwrap();
+        let parsed: Manifest = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, manifest);
+
+        let e = IntegrationTestEnvironment::from_manifest("original", &manifest);
+        let copy = IntegrationTestEnvironment::from_manifest("copy", &parsed);
+        assert_eq!(copy.to_manifest(), e.to_manifest());
+        e.setup();
+        copy.setup();
+        assert!(e.diff_with(&copy).is_empty(), "{}", e.diff_with(&copy));
+        assert_eq!(
+            std::fs::read(copy.path().unwrap().join("data/blob.bin")).unwrap(),
+            b"\x00\xffbinary"
+        );
+        #[cfg(unix)]
+        copy.assert_mode("bin/run.sh", 0o755);
+    }
+
+    #[test]
+    fn from_manifest_file() {
+        let dir = TempDir::new("manifest").unwrap();
+        let path = dir.path().join("manifest.json");
+        write(
+            &path,
+            r#"{"entries": [{"path": "out", "kind": "dir"}, {"path": "in.txt", "kind": "file", "content": "input"}]}"#,
+        )
+        .unwrap();
+        let e = IntegrationTestEnvironment::from_manifest_file("test", &path).unwrap();
+        e.setup();
+        assert_eq!(e.read_file("in.txt"), "input");
+        assert!(e.path().unwrap().join("out").is_dir());
+
+        write(&path, "{").unwrap();
+        let error = IntegrationTestEnvironment::from_manifest_file("test", &path).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::IntegrationTestEnvironment;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    Preserve,
+    NormalizeToLf,
+    NormalizeToCrLf,
+}
+
+impl NewlinePolicy {
+    pub(crate) fn apply(&self, content: String) -> String {
+        match self {
+            NewlinePolicy::Preserve => content,
+            NewlinePolicy::NormalizeToLf => content.replace("\r\n", "\n"),
+            NewlinePolicy::NormalizeToCrLf => content.replace("\r\n", "\n").replace('\n', "\r\n"),
+        }
+    }
+}
+
+impl IntegrationTestEnvironment {
+    // Only applies to what `read_file` and `read_lines` return, files on disk are untouched.
+    pub fn set_newline_policy(&mut self, policy: NewlinePolicy) {
+        self.newline_policy = policy;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{IntegrationTestEnvironment, NewlinePolicy};
+
+    #[test]
+    fn newline_policy() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("mixed", "unix\nwindows\r\nlast");
+        e.setup();
+        assert_eq!(e.read_file("mixed"), "unix\nwindows\r\nlast");
+
+        e.set_newline_policy(NewlinePolicy::NormalizeToLf);
+        assert_eq!(e.read_file("mixed"), "unix\nwindows\nlast");
+        assert_eq!(e.read_lines("mixed"), vec!["unix", "windows", "last"]);
+
+        e.set_newline_policy(NewlinePolicy::NormalizeToCrLf);
+        assert_eq!(e.read_file("mixed"), "unix\r\nwindows\r\nlast");
+        assert_eq!(e.read_lines("mixed"), vec!["unix", "windows", "last"]);
+
+        e.set_update_file_time("mixed").unwrap();
+        let on_disk = std::fs::read(e.path().unwrap().join("mixed")).unwrap();
+        assert_eq!(on_disk, b"unix\nwindows\r\nlast");
+    }
+}
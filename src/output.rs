@@ -0,0 +1,251 @@
+use crate::assert::failure_report;
+use crate::diff::unified_diff;
+use predicates::Predicate;
+use std::borrow::Cow;
+use std::ops::Deref;
+use std::process::Output;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutcome {
+    output: Output,
+}
+
+impl CommandOutcome {
+    pub(crate) fn new(output: Output) -> Self {
+        Self { output }
+    }
+
+    pub fn into_output(self) -> Output {
+        self.output
+    }
+}
+
+impl Deref for CommandOutcome {
+    type Target = Output;
+
+    fn deref(&self) -> &Output {
+        &self.output
+    }
+}
+
+pub trait OutputExt {
+    fn stdout_str(&self) -> Cow<'_, str>;
+    fn stderr_str(&self) -> Cow<'_, str>;
+    fn stdout_lines(&self) -> Vec<&str>;
+    fn success(&self) -> bool;
+    fn code(&self) -> Option<i32>;
+}
+
+impl OutputExt for Output {
+    fn stdout_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    fn stderr_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+
+    // Lines borrow from stdout, which therefore has to be valid UTF-8.
+    fn stdout_lines(&self) -> Vec<&str> {
+        std::str::from_utf8(&self.stdout)
+            .unwrap_or_else(|error| panic!("fail to split stdout into lines: {}", error))
+            .lines()
+            .collect()
+    }
+
+    fn success(&self) -> bool {
+        self.status.success()
+    }
+
+    fn code(&self) -> Option<i32> {
+        self.status.code()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMatch {
+    Exact,
+    ExactUntrimmed,
+    Contains,
+}
+
+pub fn format_output(output: &Output) -> String {
+    format!(
+        "[stderr: {} bytes]\n{}\n---------------------------\n[stdout: {} bytes]\n{}\n---------------------------\n{}",
+        output.stderr.len(),
+        String::from_utf8_lossy(&output.stderr),
+        output.stdout.len(),
+        String::from_utf8_lossy(&output.stdout),
+        output.status
+    )
+}
+
+fn trim_newline(value: &str) -> &str {
+    value
+        .strip_suffix("\r\n")
+        .or_else(|| value.strip_suffix('\n'))
+        .unwrap_or(value)
+}
+
+pub fn assert_output(output: &Output, expected: &str, mode: OutputMatch) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (matches, report) = match mode {
+        OutputMatch::Exact => {
+            let (expected, actual) = (trim_newline(expected), trim_newline(&stdout));
+            (
+                expected == actual,
+                unified_diff(expected, actual, "expected", "stdout"),
+            )
+        }
+        OutputMatch::ExactUntrimmed => (
+            expected == stdout,
+            unified_diff(expected, &stdout, "expected", "stdout"),
+        ),
+        OutputMatch::Contains => (
+            stdout.contains(expected),
+            format!("expected stdout to contain:\n{}\n", expected),
+        ),
+    };
+    if !matches {
+        let report = if report.is_empty() {
+            "stdout differs only by trailing newline\n".to_string()
+        } else {
+            report
+        };
+        panic!("stdout mismatch\n{}{}", report, format_output(output));
+    }
+}
+
+// The predicate sees the whole stdout, trailing newline included.
+pub fn assert_output_matches(output: &Output, pred: impl Predicate<str>) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if !pred.eval(&stdout) {
+        panic!(
+            "stdout mismatch\n{}\n{}",
+            failure_report(&pred, stdout.as_ref()),
+            format_output(output)
+        );
+    }
+}
+
+// Outputs are built from raw unix exit statuses.
+#[cfg(all(test, unix))]
+mod test {
+    use crate::{assert_output, format_output, OutputExt, OutputMatch};
+    use predicates::prelude::PredicateBooleanExt;
+    use predicates::str::{contains, ends_with};
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::Output;
+
+    fn output(stdout: &str, stderr: &str) -> Output {
+        Output {
+            status: ExitStatusExt::from_raw(0),
+            stdout: stdout.as_bytes().to_vec(),
+            stderr: stderr.as_bytes().to_vec(),
+        }
+    }
+
+    #[test]
+    fn format_output_lossy() {
+        let output = Output {
+            status: ExitStatusExt::from_raw(0),
+            stdout: b"hello".to_vec(),
+            stderr: vec![b'e', 0xff, b'r'],
+        };
+        assert_eq!(
+            format_output(&output),
+            "[stderr: 3 bytes]\ne\u{fffd}r\n---------------------------\n[stdout: 5 bytes]\nhello\n---------------------------\nexit status: 0"
+        );
+    }
+
+    #[test]
+    fn format_output_sections() {
+        let output = Output {
+            status: ExitStatusExt::from_raw(2 << 8),
+            stdout: b"result\n".to_vec(),
+            stderr: b"failure".to_vec(),
+        };
+        let formatted = format_output(&output);
+        assert!(formatted.starts_with("[stderr: 7 bytes]\nfailure\n"));
+        assert!(formatted.contains("---------------------------\n[stdout: 7 bytes]\nresult\n\n"));
+        assert!(formatted.ends_with("---------------------------\nexit status: 2"));
+    }
+
+    #[test]
+    fn assert_output_modes() {
+        let out = output("line 1\nline 2\n", "");
+        assert_output(&out, "line 1\nline 2", OutputMatch::Exact);
+        assert_output(&out, "line 1\nline 2\n", OutputMatch::Exact);
+        assert_output(&out, "line 1\nline 2\n", OutputMatch::ExactUntrimmed);
+        assert_output(&out, "line 2", OutputMatch::Contains);
+        crate::assert_output!(out, "line 1\nline 2");
+        crate::assert_output!(out, contains "line 1");
+        crate::assert_output!(out, untrimmed "line 1\nline 2\n");
+        crate::assert_output!(out, matches contains("line 1").and(ends_with("2\n")));
+    }
+
+    #[test]
+    fn assert_output_diff() {
+        let out = output("line 1\nline 2\n", "warning");
+        let result = std::panic::catch_unwind(|| {
+            crate::assert_output!(out, "line 1\nline two\n");
+        });
+        let error = result.unwrap_err();
+        let message = error.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("stdout mismatch\n--- expected\n+++ stdout\n"));
+        assert!(message.contains("-line two\n+line 2\n"));
+    }
+
+    #[test]
+    #[should_panic(expected = "stdout mismatch\nvar.contains(line 3)")]
+    fn assert_output_predicate_mismatch() {
+        let out = output("line 1\n", "warning");
+        crate::assert_output!(out, matches contains("line 3"));
+    }
+
+    #[test]
+    #[should_panic(expected = "[stderr: 7 bytes]\nwarning\n")]
+    fn assert_output_reports_stderr() {
+        let out = output("line 1\n", "warning");
+        crate::assert_output!(out, contains "line 2");
+    }
+
+    #[test]
+    #[should_panic(expected = "stdout mismatch\nstdout differs only by trailing newline\n")]
+    fn assert_output_untrimmed() {
+        let out = output("line 1\n", "");
+        crate::assert_output!(out, untrimmed "line 1");
+    }
+
+    #[test]
+    fn output_ext_lossy() {
+        let out = Output {
+            status: ExitStatusExt::from_raw(0),
+            stdout: vec![b'o', 0xff, b'k'],
+            stderr: vec![0xfe],
+        };
+        assert_eq!(out.stdout_str(), "o\u{fffd}k");
+        assert_eq!(out.stderr_str(), "\u{fffd}");
+        assert!(out.success());
+        assert_eq!(out.code(), Some(0));
+    }
+
+    #[test]
+    fn output_ext_lines() {
+        assert_eq!(output("a\nb\n", "").stdout_lines(), vec!["a", "b"]);
+        assert_eq!(output("a\nb", "").stdout_lines(), vec!["a", "b"]);
+        assert_eq!(output("a\r\n\nb", "").stdout_lines(), vec!["a", "", "b"]);
+        assert!(output("", "").stdout_lines().is_empty());
+    }
+
+    #[test]
+    fn output_ext_failure() {
+        let out = Output {
+            status: ExitStatusExt::from_raw(3 << 8),
+            stdout: vec![],
+            stderr: vec![],
+        };
+        assert!(!out.success());
+        assert_eq!(out.code(), Some(3));
+    }
+}
@@ -0,0 +1,96 @@
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+// Lexical check only: `a/../b` is accepted as `b`, symbolic links are not resolved.
+pub(crate) fn normalize(path: &Path) -> Result<PathBuf, &'static str> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(component) => normalized.push(component),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err("paths escaping the environment root are rejected");
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => {
+                return Err(
+                    "absolute paths are rejected, use a path relative to the environment root",
+                )
+            }
+        }
+    }
+    Ok(normalized)
+}
+
+pub(crate) fn checked_path(path: &Path) -> io::Result<PathBuf> {
+    normalize(path).map_err(|reason| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{:?}: {}", path, reason),
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::normalize;
+    use crate::IntegrationTestEnvironment;
+    use std::io;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn normalize_paths() {
+        assert_eq!(normalize(Path::new("a/./b")), Ok(PathBuf::from("a/b")));
+        assert_eq!(normalize(Path::new("a/../b")), Ok(PathBuf::from("b")));
+        assert!(normalize(Path::new("../sibling")).is_err());
+        assert!(normalize(Path::new("a/../../sibling")).is_err());
+        assert!(normalize(Path::new("/etc/test_config")).is_err());
+    }
+
+    #[test]
+    fn internal_parent_dir() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("a/../b", "normalized");
+        e.setup();
+        assert_eq!(e.planned_entries(), vec![(PathBuf::from("b"), false)]);
+        assert_eq!(e.read_file("a/../b"), "normalized");
+        e.write_file("c/../b", "written").unwrap();
+        assert_eq!(e.read_file("b"), "written");
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to add \"/etc/test_config\": absolute paths are rejected")]
+    fn add_file_absolute() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("/etc/test_config", "");
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to add \"../sibling\": paths escaping the environment root")]
+    fn add_dir_parent() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_dir("../sibling");
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to read file \"../sibling\": paths escaping")]
+    fn read_file_parent() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.read_file("../sibling");
+    }
+
+    #[test]
+    fn live_paths() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.setup();
+        for error in [
+            e.write_file("../sibling", "").unwrap_err(),
+            e.touch("/tmp/touched").unwrap_err(),
+            e.delete_file("../sibling").unwrap_err(),
+            e.make_executable("../sibling").unwrap_err(),
+        ] {
+            assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,257 @@
+use crate::entry::Entry;
+use crate::{display_path, CopyMode, IntegrationTestEnvironment};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+// Paths are relative to the environment root. A directory is created with its missing
+// ancestors, which are not listed on their own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PlannedOp {
+    CreateDir {
+        path: PathBuf,
+    },
+    // Generated contents are not produced by `plan()`, their size is unknown.
+    WriteFile {
+        path: PathBuf,
+        bytes: Option<usize>,
+    },
+    CopyFile {
+        path: PathBuf,
+        source: PathBuf,
+        mode: CopyMode,
+    },
+    HardLink {
+        path: PathBuf,
+        target: PathBuf,
+    },
+    CreateFifo {
+        path: PathBuf,
+    },
+    #[cfg(feature = "sqlite")]
+    CreateSqliteDb {
+        path: PathBuf,
+    },
+    SetMode {
+        path: PathBuf,
+        mode: u32,
+    },
+}
+
+impl Display for PlannedOp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PlannedOp::CreateDir { path } => write!(f, "create dir {}", display_path(path)),
+            PlannedOp::WriteFile {
+                path,
+                bytes: Some(bytes),
+            } => write!(f, "write file {} ({} bytes)", display_path(path), bytes),
+            PlannedOp::WriteFile { path, bytes: None } => {
+                write!(f, "write file {} (generated content)", display_path(path))
+            }
+            PlannedOp::CopyFile { path, source, mode } => write!(
+                f,
+                "copy file {} from {} ({})",
+                display_path(path),
+                display_path(source),
+                mode.name()
+            ),
+            PlannedOp::HardLink { path, target } => write!(
+                f,
+                "hard link {} -> {}",
+                display_path(path),
+                display_path(target)
+            ),
+            PlannedOp::CreateFifo { path } => write!(f, "create fifo {}", display_path(path)),
+            #[cfg(feature = "sqlite")]
+            PlannedOp::CreateSqliteDb { path } => {
+                write!(f, "create sqlite database {}", display_path(path))
+            }
+            PlannedOp::SetMode { path, mode } => {
+                write!(f, "set mode {} {:o}", display_path(path), mode)
+            }
+        }
+    }
+}
+
+impl IntegrationTestEnvironment {
+    // The operations are listed in the order `setup()` runs them: every directory, the
+    // files, the copies, the hard links since their target may be staged later, the
+    // fifos, the databases and the modes.
+    pub fn plan(&self) -> Vec<PlannedOp> {
+        let mut created_dirs = HashSet::new();
+        let mut ops = vec![];
+        let mut create_dir = |path: &Path, ops: &mut Vec<PlannedOp>| {
+            if !path.as_os_str().is_empty() && created_dirs.insert(path.to_path_buf()) {
+                ops.push(PlannedOp::CreateDir {
+                    path: path.to_path_buf(),
+                });
+            }
+        };
+        let mut links = vec![];
+        for (path, entry) in self.sorted_entries() {
+            let path = path.clone();
+            let op = match entry {
+                Entry::Dir => {
+                    create_dir(&path, &mut ops);
+                    continue;
+                }
+                Entry::Hardlink(target) => {
+                    links.push(PlannedOp::HardLink {
+                        path,
+                        target: target.clone(),
+                    });
+                    continue;
+                }
+                Entry::File(content) => PlannedOp::WriteFile {
+                    path,
+                    bytes: Some(content.len()),
+                },
+                Entry::Bytes(content) => PlannedOp::WriteFile {
+                    path,
+                    bytes: Some(content.len()),
+                },
+                Entry::Generated(_) => PlannedOp::WriteFile { path, bytes: None },
+                Entry::Fifo => PlannedOp::CreateFifo { path },
+                #[cfg(feature = "sqlite")]
+                Entry::Sqlite(_) => PlannedOp::CreateSqliteDb { path },
+                Entry::Copy { source, mode } => PlannedOp::CopyFile {
+                    path,
+                    source: source.clone(),
+                    mode: *mode,
+                },
+            };
+            if let Some(parent) = op.path().parent() {
+                create_dir(parent, &mut ops);
+            }
+            ops.push(op);
+        }
+        for link in links {
+            if let Some(parent) = link.path().parent() {
+                create_dir(parent, &mut ops);
+            }
+            ops.push(link);
+        }
+        for (path, mode) in self.sorted_modes() {
+            ops.push(PlannedOp::SetMode {
+                path: path.clone(),
+                mode: *mode,
+            });
+        }
+        ops.sort_by_key(PlannedOp::stage);
+        ops
+    }
+
+    pub fn dry_run(&self) -> Vec<String> {
+        self.plan().iter().map(|op| self.describe_op(op)).collect()
+    }
+
+    // The content of binary files is previewed, it is the only one `plan()` has at hand.
+    pub(crate) fn describe_op(&self, op: &PlannedOp) -> String {
+        match (op, self.entries.get(op.path())) {
+            (PlannedOp::WriteFile { .. }, Some(entry @ Entry::Bytes(_))) => {
+                format!("{} {}", op, entry.lossy_preview())
+            }
+            _ => op.to_string(),
+        }
+    }
+}
+
+impl PlannedOp {
+    fn stage(&self) -> u8 {
+        match self {
+            PlannedOp::CreateDir { .. } => 0,
+            PlannedOp::WriteFile { .. } => 1,
+            PlannedOp::CopyFile { .. } => 2,
+            PlannedOp::HardLink { .. } => 3,
+            PlannedOp::CreateFifo { .. } => 4,
+            #[cfg(feature = "sqlite")]
+            PlannedOp::CreateSqliteDb { .. } => 5,
+            PlannedOp::SetMode { .. } => 6,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            PlannedOp::CreateDir { path }
+            | PlannedOp::WriteFile { path, .. }
+            | PlannedOp::CopyFile { path, .. }
+            | PlannedOp::HardLink { path, .. }
+            | PlannedOp::CreateFifo { path }
+            | PlannedOp::SetMode { path, .. } => path,
+            #[cfg(feature = "sqlite")]
+            PlannedOp::CreateSqliteDb { path } => path,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::entry::Entry;
+    use crate::{IntegrationTestEnvironment, PlannedOp};
+    use std::cell::RefCell;
+    use std::path::PathBuf;
+
+    #[test]
+    fn plan_nested_file() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_executable_file("bin/tool", "echo");
+        e.add_file("config.toml", "key = 1");
+        assert_eq!(
+            e.plan(),
+            vec![
+                PlannedOp::CreateDir {
+                    path: PathBuf::from("bin")
+                },
+                PlannedOp::WriteFile {
+                    path: PathBuf::from("bin/tool"),
+                    bytes: Some(4)
+                },
+                PlannedOp::WriteFile {
+                    path: PathBuf::from("config.toml"),
+                    bytes: Some(7)
+                },
+                PlannedOp::SetMode {
+                    path: PathBuf::from("bin/tool"),
+                    mode: 0o755
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_binary_preview() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("config.toml", "key = 1");
+        e.stage(
+            PathBuf::from("data/blob.bin"),
+            Entry::Bytes(b"key = 1\0\xff".to_vec()),
+        );
+        assert_eq!(
+            e.dry_run(),
+            vec![
+                "create dir data",
+                "write file config.toml (7 bytes)",
+                "write file data/blob.bin (9 bytes) \"key = 1\\0\u{fffd}\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn setup_verbose() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("a/b/c.txt", "content");
+        e.add_file_with("a/generated", || "generated".to_string());
+        e.add_executable_file("bin/run.sh", "echo run");
+        let lines = RefCell::new(vec![]);
+        e.setup_with(&|op| {
+            let path = e.path().unwrap().join(op.path());
+            assert!(path.exists(), "{:?} is reported before it exists", path);
+            lines.borrow_mut().push(e.describe_op(op));
+        });
+        assert_eq!(lines.into_inner(), e.dry_run());
+        assert_eq!(e.setup_verbose(), e.plan());
+        assert_eq!(e.read_file("a/generated"), "generated");
+    }
+}
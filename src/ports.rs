@@ -0,0 +1,88 @@
+use crate::process::POLL_INTERVAL;
+use crate::IntegrationTestEnvironment;
+use std::collections::hash_map::Entry;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+impl IntegrationTestEnvironment {
+    // The listener keeps the port reserved until `release_port`, released ports are
+    // remembered so that the environment never hands the same port out twice.
+    pub fn reserve_port(&mut self) -> u16 {
+        loop {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                .unwrap_or_else(|error| panic!("fail to reserve a port: {}", error));
+            let port = listener
+                .local_addr()
+                .unwrap_or_else(|error| panic!("fail to reserve a port: {}", error))
+                .port();
+            if let Entry::Vacant(reserved) = self.ports.entry(port) {
+                reserved.insert(Some(listener));
+                return port;
+            }
+        }
+    }
+
+    // To be called right before starting the command that binds the port.
+    pub fn release_port(&mut self, port: u16) -> bool {
+        match self.ports.get_mut(&port) {
+            Some(listener) => listener.take().is_some(),
+            None => false,
+        }
+    }
+
+    pub fn wait_for_port(&self, port: u16, timeout: Duration) -> bool {
+        let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+        let start = Instant::now();
+        loop {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            if TcpStream::connect_timeout(&address, remaining.max(POLL_INTERVAL)).is_ok() {
+                return true;
+            }
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::collections::HashSet;
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::thread::{sleep, spawn};
+    use std::time::Duration;
+
+    #[test]
+    fn reserve_port() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        let ports: HashSet<u16> = (0..5).map(|_| e.reserve_port()).collect();
+        assert_eq!(ports.len(), 5);
+        let port = *ports.iter().next().unwrap();
+        assert!(TcpListener::bind((Ipv4Addr::LOCALHOST, port)).is_err());
+
+        assert!(e.release_port(port));
+        assert!(!e.release_port(port));
+        assert!(!e.release_port(0));
+        TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+        assert!(!ports.contains(&e.reserve_port()));
+    }
+
+    #[test]
+    fn wait_for_port() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        let port = e.reserve_port();
+        e.release_port(port);
+        assert!(!e.wait_for_port(port, Duration::from_millis(50)));
+
+        let server = spawn(move || {
+            sleep(Duration::from_millis(100));
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port)).unwrap();
+            listener.accept().unwrap();
+        });
+        assert!(e.wait_for_port(port, Duration::from_secs(10)));
+        server.join().unwrap();
+    }
+}
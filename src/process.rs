@@ -0,0 +1,182 @@
+use crate::paths::checked_path;
+use crate::{CargoBinError, CommandOutcome, IntegrationTestEnvironment};
+use assert_cmd::Command;
+use std::fs::read;
+use std::io;
+use std::path::Path;
+use std::process::{Child, Output};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(10);
+pub(crate) const CONCURRENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+impl IntegrationTestEnvironment {
+    pub fn spawn<C: AsRef<str>>(&self, crate_name: C, args: &[&str]) -> io::Result<Child> {
+        let mut command = match self.spawnable_command(crate_name.as_ref()) {
+            Ok(command) => command,
+            Err(CargoBinError::Io(error)) => return Err(error),
+            Err(error) => return Err(io::Error::new(io::ErrorKind::NotFound, error.to_string())),
+        };
+        command.args(args).spawn()
+    }
+
+    pub fn run_with_stdin_file<C, P>(
+        &self,
+        crate_name: C,
+        args: &[&str],
+        stdin_path: P,
+    ) -> CommandOutcome
+    where
+        C: AsRef<str>,
+        P: AsRef<Path>,
+    {
+        let stdin_path = self.live_path(stdin_path.as_ref(), "read stdin file");
+        let stdin =
+            read(&stdin_path).unwrap_or_else(|_| panic!("fail to read file {:?}", stdin_path));
+        let output = self
+            .command_args(crate_name.as_ref(), args)
+            .write_stdin(stdin)
+            .output()
+            .unwrap_or_else(|_| panic!("fail to run {:?}", crate_name.as_ref()));
+        CommandOutcome::new(output)
+    }
+
+    pub fn command_with_stdin<C, B>(&self, crate_name: C, stdin: B) -> Command
+    where
+        C: AsRef<str>,
+        B: Into<Vec<u8>>,
+    {
+        let mut command = self
+            .command(crate_name.as_ref())
+            .expect("fail to create command");
+        command.write_stdin(stdin);
+        command
+    }
+
+    // `assert_cmd` writes stdin from its own thread while the outputs are read, large
+    // payloads can not fill the pipes and block the child.
+    pub fn run_with_stdin(&self, mut command: Command, stdin: impl Into<Vec<u8>>) -> Output {
+        command
+            .write_stdin(stdin)
+            .output()
+            .unwrap_or_else(|error| panic!("fail to run command: {}", error))
+    }
+
+    pub fn run<C: AsRef<str>>(&self, crate_name: C, args: &[&str]) -> CommandOutcome {
+        self.run_timed(crate_name, args).0
+    }
+
+    // Only the child process lifetime is measured, not building the command.
+    pub fn run_timed<C: AsRef<str>>(
+        &self,
+        crate_name: C,
+        args: &[&str],
+    ) -> (CommandOutcome, Duration) {
+        let command = self.command_args(crate_name.as_ref(), args);
+        let (output, elapsed) = self.timed_run(command);
+        self.record(crate_name.as_ref(), args, &output, elapsed);
+        (CommandOutcome::new(output), elapsed)
+    }
+
+    // The duration is also reported by the failure dumps of the environment.
+    pub fn timed_run(&self, mut command: Command) -> (Output, Duration) {
+        let start = Instant::now();
+        let output = command
+            .output()
+            .unwrap_or_else(|error| panic!("fail to run command: {}", error));
+        let elapsed = start.elapsed();
+        self.record_timed_invocation(&command, elapsed);
+        (output, elapsed)
+    }
+
+    // A child still running after the timeout is killed, its output is what it wrote so far.
+    pub fn set_concurrent_timeout(&mut self, timeout: Duration) {
+        self.concurrent_timeout = timeout;
+    }
+
+    // Commands are built in index order on the calling thread, so `build` can borrow the
+    // environment, then each child runs on its own thread. Outputs keep the index order,
+    // the children that can not be run are reported once every other one is collected.
+    pub fn run_concurrently<F>(&self, n: usize, build: F) -> Vec<Output>
+    where
+        F: Fn(usize) -> Command,
+    {
+        let commands: Vec<Command> = (0..n)
+            .map(|index| {
+                let mut command = build(index);
+                command.timeout(self.concurrent_timeout);
+                command
+            })
+            .collect();
+        let results: Vec<io::Result<Output>> = std::thread::scope(|scope| {
+            let children: Vec<_> = commands
+                .into_iter()
+                .map(|mut command| scope.spawn(move || command.output()))
+                .collect();
+            children
+                .into_iter()
+                .map(|child| child.join().expect("command runner panicked"))
+                .collect()
+        });
+        let mut errors = vec![];
+        let outputs = results
+            .into_iter()
+            .enumerate()
+            .map(|(index, result)| {
+                result.unwrap_or_else(|error| {
+                    errors.push(format!("fail to run command {}: {}", index, error));
+                    not_run()
+                })
+            })
+            .collect();
+        self.report_errors(errors);
+        outputs
+    }
+
+    pub fn wait_for_file<P: AsRef<Path>>(&self, path: P, timeout: Duration) -> io::Result<()> {
+        let path = self.tmp_dir.path().join(checked_path(path.as_ref())?);
+        let start = Instant::now();
+        while !path.exists() {
+            if start.elapsed() >= timeout {
+                return Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("file {:?} did not appear within {:?}", path, timeout),
+                ));
+            }
+            sleep(POLL_INTERVAL);
+        }
+        Ok(())
+    }
+}
+
+// Stands for a child that could not be run when the errors are only recorded.
+fn not_run() -> Output {
+    #[cfg(unix)]
+    let status = std::os::unix::process::ExitStatusExt::from_raw(1 << 8);
+    #[cfg(windows)]
+    let status = std::os::windows::process::ExitStatusExt::from_raw(1);
+    Output {
+        status,
+        stdout: vec![],
+        stderr: vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::io;
+    use std::time::Duration;
+
+    #[test]
+    fn wait_for_file_timeout() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.setup();
+        let error = e
+            .wait_for_file("never", Duration::from_millis(50))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(error.to_string().contains("did not appear within 50ms"));
+    }
+}
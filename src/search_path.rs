@@ -0,0 +1,97 @@
+use crate::IntegrationTestEnvironment;
+use std::env;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathMode {
+    Prepend,
+    Append,
+    // The inherited PATH is dropped, only the directories of the environment are searched.
+    Replace,
+}
+
+impl IntegrationTestEnvironment {
+    // Directories are searched in call order, before the inherited `PATH`
+    // (or the one given to `set_env`).
+    pub fn prepend_path<P: AsRef<Path>>(&mut self, subdir: P) {
+        self.set_path_dir(subdir, PathMode::Prepend);
+    }
+
+    pub fn set_path_dir<P: AsRef<Path>>(&mut self, subdir: P, mode: PathMode) {
+        self.path_dirs.push((subdir.as_ref().to_path_buf(), mode));
+    }
+
+    pub(crate) fn command_path(&self) -> Option<OsString> {
+        if self.path_dirs.is_empty() {
+            return None;
+        }
+        let dir = |subdir: &PathBuf| self.tmp_dir.path().join(subdir);
+        let mut paths: Vec<PathBuf> = self
+            .path_dirs
+            .iter()
+            .filter(|(_, mode)| *mode != PathMode::Append)
+            .map(|(subdir, _)| dir(subdir))
+            .collect();
+        let replaced = self
+            .path_dirs
+            .iter()
+            .any(|(_, mode)| *mode == PathMode::Replace);
+        if !replaced {
+            let inherited = match self.envs.get("PATH") {
+                Some(path) => Some(OsString::from(path)),
+                None => env::var_os("PATH"),
+            };
+            if let Some(inherited) = inherited {
+                paths.extend(env::split_paths(&inherited));
+            }
+        }
+        paths.extend(
+            self.path_dirs
+                .iter()
+                .filter(|(_, mode)| *mode == PathMode::Append)
+                .map(|(subdir, _)| dir(subdir)),
+        );
+        Some(env::join_paths(paths).expect("fail to join PATH entries"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{IntegrationTestEnvironment, PathMode};
+    use std::env;
+    use std::path::PathBuf;
+
+    fn command_path(e: &IntegrationTestEnvironment) -> Vec<PathBuf> {
+        env::split_paths(&e.command_path().unwrap()).collect()
+    }
+
+    #[test]
+    fn path_modes() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.set_env("PATH", "/usr/bin");
+        e.set_path_dir("last", PathMode::Append);
+        e.set_path_dir("first", PathMode::Prepend);
+        let root = e.temp_dir().path().to_path_buf();
+        assert_eq!(
+            command_path(&e),
+            vec![
+                root.join("first"),
+                PathBuf::from("/usr/bin"),
+                root.join("last")
+            ]
+        );
+
+        e.set_path_dir("bin", PathMode::Replace);
+        assert_eq!(
+            command_path(&e),
+            vec![root.join("first"), root.join("bin"), root.join("last")]
+        );
+    }
+
+    #[test]
+    fn no_path_dirs() {
+        let e = IntegrationTestEnvironment::new("test");
+        assert_eq!(e.command_path(), None);
+    }
+}
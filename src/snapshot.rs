@@ -0,0 +1,159 @@
+use crate::hash::sha256_file;
+use crate::{display_path, IntegrationTestEnvironment};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct SnapshotEntry {
+    is_dir: bool,
+    size: u64,
+    mtime: Option<SystemTime>,
+    hash: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TreeSnapshot {
+    entries: BTreeMap<PathBuf, SnapshotEntry>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Changes {
+    pub created: Vec<PathBuf>,
+    pub modified: Vec<PathBuf>,
+    pub deleted: Vec<PathBuf>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.modified.is_empty() && self.deleted.is_empty()
+    }
+}
+
+impl Display for Changes {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        for (title, sign, paths) in [
+            ("created", '+', &self.created),
+            ("modified", '~', &self.modified),
+            ("deleted", '-', &self.deleted),
+        ] {
+            if paths.is_empty() {
+                continue;
+            }
+            writeln!(f, "{} ({}):", title, paths.len())?;
+            for path in paths {
+                writeln!(f, "  {} {}", sign, display_path(path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl IntegrationTestEnvironment {
+    pub fn capture(&self) -> TreeSnapshot {
+        let mut entries = BTreeMap::new();
+        for relative in self.full_tree() {
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+            let path = self.tmp_dir.path().join(&relative);
+            let metadata = path
+                .symlink_metadata()
+                .unwrap_or_else(|_| panic!("fail to read metadata {:?}", path));
+            let entry = if metadata.is_dir() {
+                SnapshotEntry {
+                    is_dir: true,
+                    size: 0,
+                    mtime: None,
+                    hash: None,
+                }
+            } else {
+                SnapshotEntry {
+                    is_dir: false,
+                    size: metadata.len(),
+                    mtime: metadata.modified().ok(),
+                    // Reading a named pipe would block.
+                    hash: if metadata.is_file() {
+                        sha256_file(&path).ok()
+                    } else {
+                        None
+                    },
+                }
+            };
+            entries.insert(relative, entry);
+        }
+        TreeSnapshot { entries }
+    }
+
+    // Directories only count as created or deleted: their mtime moves every time a child
+    // changes, which would drown the actual file modifications.
+    pub fn changes_since(&self, snapshot: &TreeSnapshot) -> Changes {
+        let current = self.capture();
+        let mut changes = Changes::default();
+        for (path, entry) in current.entries.iter() {
+            match snapshot.entries.get(path) {
+                None => changes.created.push(path.clone()),
+                Some(previous) if previous != entry => changes.modified.push(path.clone()),
+                Some(_) => {}
+            }
+        }
+        for path in snapshot.entries.keys() {
+            if !current.entries.contains_key(path) {
+                changes.deleted.push(path.clone());
+            }
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+    use std::fs::{remove_dir, write, File};
+    use std::path::PathBuf;
+
+    #[test]
+    fn changes_since() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("unchanged", "same");
+        e.add_file("modified", "aaaa");
+        e.add_file("deleted", "bye");
+        e.add_dir("empty_dir");
+        e.setup();
+        let snapshot = e.capture();
+        assert!(e.changes_since(&snapshot).is_empty());
+
+        let root = e.path().unwrap();
+        // Same size and same mtime, only the content hash tells the file changed.
+        let modified = root.join("modified");
+        let mtime = modified.metadata().unwrap().modified().unwrap();
+        write(&modified, "bbbb").unwrap();
+        File::options()
+            .write(true)
+            .open(&modified)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+        assert_eq!(modified.metadata().unwrap().modified().unwrap(), mtime);
+        write(root.join("created"), "new").unwrap();
+        std::fs::remove_file(root.join("deleted")).unwrap();
+        remove_dir(root.join("empty_dir")).unwrap();
+
+        let changes = e.changes_since(&snapshot);
+        assert_eq!(changes.created, vec![PathBuf::from("created")]);
+        assert_eq!(changes.modified, vec![PathBuf::from("modified")]);
+        assert_eq!(
+            changes.deleted,
+            vec![PathBuf::from("deleted"), PathBuf::from("empty_dir")]
+        );
+
+        let display = changes.to_string();
+        assert!(display.contains("created (1):\n  + created\n"));
+        assert!(display.contains("modified (1):\n  ~ modified\n"));
+        assert!(display.contains("deleted (2):\n  - deleted\n  - empty_dir\n"));
+    }
+}
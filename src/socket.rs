@@ -0,0 +1,105 @@
+use crate::process::POLL_INTERVAL;
+use crate::IntegrationTestEnvironment;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use tempdir::TempDir;
+
+// `sun_path` holds 104 bytes on macOS and 108 on Linux, including the trailing NUL.
+const SOCKET_PATH_MAX: usize = 103;
+
+impl IntegrationTestEnvironment {
+    // Inside the environment root when it fits, otherwise in a short directory under
+    // `/tmp` that is removed with the environment.
+    pub fn socket_path<N: AsRef<str>>(&self, name: N) -> PathBuf {
+        let name = name.as_ref();
+        let path = self.canonical_path().join(name);
+        if path.as_os_str().len() <= SOCKET_PATH_MAX {
+            return path;
+        }
+        let mut socket_dir = self.socket_dir.borrow_mut();
+        if socket_dir.is_none() {
+            let dir = TempDir::new_in("/tmp", "sock")
+                .unwrap_or_else(|error| panic!("fail to create a socket directory: {}", error));
+            *socket_dir = Some(dir);
+        }
+        let dir = socket_dir.as_ref().expect("socket directory").path();
+        let path = dir
+            .canonicalize()
+            .unwrap_or_else(|_| dir.to_path_buf())
+            .join(name);
+        if path.as_os_str().len() > SOCKET_PATH_MAX {
+            panic!(
+                "fail to create a socket path for {:?}: {:?} exceeds {} bytes",
+                name, path, SOCKET_PATH_MAX
+            );
+        }
+        path
+    }
+
+    pub fn wait_for_socket<P: AsRef<Path>>(&self, path: P, timeout: Duration) -> bool {
+        let start = Instant::now();
+        loop {
+            if UnixStream::connect(path.as_ref()).is_ok() {
+                return true;
+            }
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            sleep(POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SOCKET_PATH_MAX;
+    use crate::IntegrationTestEnvironment;
+    use std::os::unix::net::UnixListener;
+    use std::thread::{sleep, spawn};
+    use std::time::Duration;
+
+    #[test]
+    fn socket_path_in_root() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.setup();
+        let path = e.socket_path("daemon.sock");
+        assert_eq!(path, e.path().unwrap().join("daemon.sock"));
+        UnixListener::bind(&path).unwrap();
+    }
+
+    #[test]
+    fn socket_path_fallback() {
+        let e = IntegrationTestEnvironment::new("a".repeat(32));
+        e.setup();
+        let name = "s".repeat(SOCKET_PATH_MAX + 1 - e.path().unwrap().as_os_str().len());
+        let path = e.socket_path(&name);
+        assert!(!path.starts_with(e.path().unwrap()));
+        assert!(path.as_os_str().len() <= SOCKET_PATH_MAX);
+        assert_eq!(e.socket_path(&name), path);
+
+        let listener = UnixListener::bind(&path).unwrap();
+        let dir = path.parent().unwrap().to_path_buf();
+        drop(listener);
+        drop(e);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn wait_for_socket() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.setup();
+        let path = e.socket_path("daemon.sock");
+        assert!(!e.wait_for_socket(&path, Duration::from_millis(50)));
+
+        let server_path = path.clone();
+        let server = spawn(move || {
+            sleep(Duration::from_millis(100));
+            let listener = UnixListener::bind(server_path).unwrap();
+            listener.accept().unwrap();
+        });
+        assert!(e.wait_for_socket(&path, Duration::from_secs(10)));
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,104 @@
+use crate::entry::Entry;
+use crate::IntegrationTestEnvironment;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use std::fs::remove_file;
+use std::io;
+use std::path::Path;
+
+impl IntegrationTestEnvironment {
+    // The database is created by `setup()`, a previous one at the same path is replaced.
+    pub fn add_sqlite_db<P: AsRef<Path>>(&mut self, path: P, schema_and_seed_sql: &str) {
+        self.stage(
+            path.as_ref().to_path_buf(),
+            Entry::Sqlite(schema_and_seed_sql.to_string()),
+        );
+    }
+
+    // Every value is rendered as text, `NULL` for null and lossy for blobs.
+    pub fn query_sqlite<P: AsRef<Path>>(&self, path: P, sql: &str) -> Vec<Vec<String>> {
+        let path = self.live_path(path.as_ref(), "query");
+        query(&path, sql)
+            .unwrap_or_else(|error| panic!("fail to execute {:?} on {:?}: {}", sql, path, error))
+    }
+}
+
+pub(crate) fn create_db(path: &Path, sql: &str) -> Result<(), String> {
+    match remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => {
+            return Err(format!("fail to remove {:?}: {}", path, error))
+        }
+        _ => {}
+    }
+    Connection::open(path)
+        .and_then(|connection| connection.execute_batch(sql))
+        .map_err(|error| format!("fail to execute {:?} on {:?}: {}", sql, path, error))
+}
+
+fn query(path: &Path, sql: &str) -> rusqlite::Result<Vec<Vec<String>>> {
+    let connection = Connection::open(path)?;
+    let mut statement = connection.prepare(sql)?;
+    let columns = statement.column_count();
+    let mut rows = statement.query([])?;
+    let mut values = vec![];
+    while let Some(row) = rows.next()? {
+        let mut row_values = Vec::with_capacity(columns);
+        for index in 0..columns {
+            row_values.push(match row.get_ref(index)? {
+                ValueRef::Null => "NULL".to_string(),
+                ValueRef::Integer(value) => value.to_string(),
+                ValueRef::Real(value) => value.to_string(),
+                ValueRef::Text(text) | ValueRef::Blob(text) => {
+                    String::from_utf8_lossy(text).into_owned()
+                }
+            });
+        }
+        values.push(row_values);
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::IntegrationTestEnvironment;
+
+    #[test]
+    fn add_sqlite_db() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_sqlite_db(
+            "data/app.db",
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT, score REAL);
+             INSERT INTO users (name, score) VALUES ('alice', 1.5), (NULL, 2);",
+        );
+        assert_eq!(
+            e.dry_run(),
+            vec!["create dir data", "create sqlite database data/app.db"]
+        );
+        e.setup();
+        e.setup();
+        assert_eq!(
+            e.query_sqlite(
+                "data/app.db",
+                "SELECT id, name, score FROM users ORDER BY id"
+            ),
+            vec![vec!["1", "alice", "1.5"], vec!["2", "NULL", "2"]]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "no such table: missing")]
+    fn add_sqlite_db_invalid_sql() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_sqlite_db("app.db", "INSERT INTO missing VALUES (1);");
+        e.setup();
+    }
+
+    #[test]
+    #[should_panic(expected = "fail to execute \"SELECT * FROM missing\"")]
+    fn query_sqlite_invalid_sql() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_sqlite_db("app.db", "CREATE TABLE users (name TEXT);");
+        e.setup();
+        e.query_sqlite("app.db", "SELECT * FROM missing");
+    }
+}
@@ -0,0 +1,168 @@
+use crate::{display_path, IntegrationTestEnvironment};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+impl IntegrationTestEnvironment {
+    // Sorted by path, without the environment root. Directories have a size of 0.
+    pub fn tree_with_metadata(&self) -> Vec<TreeEntry> {
+        self.full_tree()
+            .into_iter()
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| {
+                let full_path = self.tmp_dir.path().join(&path);
+                let metadata = full_path
+                    .symlink_metadata()
+                    .unwrap_or_else(|_| panic!("fail to read metadata {:?}", full_path));
+                TreeEntry {
+                    is_dir: metadata.is_dir(),
+                    size: if metadata.is_dir() { 0 } else { metadata.len() },
+                    path,
+                }
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn tree_json(&self) -> String {
+        let entries: Vec<serde_json::Value> = self
+            .tree_with_metadata()
+            .into_iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "path": crate::display_path(&entry.path),
+                    "is_dir": entry.is_dir,
+                    "size": entry.size,
+                })
+            })
+            .collect();
+        serde_json::to_string_pretty(&entries).expect("fail to serialize the tree")
+    }
+}
+
+const DEBUG_TREE_SEPARATOR: &str = "==========================================";
+
+// Printed by `debug_tree!`, the separators make it stand out in the middle of the test output.
+pub fn render_debug_tree(environment: &IntegrationTestEnvironment) -> String {
+    let mut rendered = format!(
+        "{}\n[{}] {}\n{}\n",
+        DEBUG_TREE_SEPARATOR,
+        environment.label(),
+        environment.tmp_dir.path().display(),
+        DEBUG_TREE_SEPARATOR
+    );
+    let entries = environment.tree_with_metadata();
+    if entries.is_empty() {
+        rendered.push_str("(empty)\n");
+    }
+    for entry in entries {
+        if entry.is_dir {
+            rendered.push_str(&format!("dir   {}/\n", display_path(&entry.path)));
+        } else {
+            rendered.push_str(&format!(
+                "file  {} ({} bytes)\n",
+                display_path(&entry.path),
+                entry.size
+            ));
+        }
+    }
+    rendered.push_str(DEBUG_TREE_SEPARATOR);
+    rendered.push('\n');
+    rendered
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{render_debug_tree, IntegrationTestEnvironment, TreeEntry};
+    use std::path::PathBuf;
+
+    fn environment() -> IntegrationTestEnvironment {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("b/file.txt", "content");
+        e.add_file("a.txt", "");
+        e.setup();
+        e
+    }
+
+    #[test]
+    fn tree_with_metadata() {
+        let e = environment();
+        assert_eq!(
+            e.tree_with_metadata(),
+            vec![
+                TreeEntry {
+                    path: PathBuf::from("a.txt"),
+                    is_dir: false,
+                    size: 0
+                },
+                TreeEntry {
+                    path: PathBuf::from("b"),
+                    is_dir: true,
+                    size: 0
+                },
+                TreeEntry {
+                    path: PathBuf::from("b/file.txt"),
+                    is_dir: false,
+                    size: 7
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn render_debug_tree_format() {
+        let e = environment();
+        assert_eq!(
+            e.strip_root(&render_debug_tree(&e)),
+            "==========================================\n\
+             [test] [ROOT]\n\
+             ==========================================\n\
+             file  a.txt (0 bytes)\n\
+             dir   b/\n\
+             file  b/file.txt (7 bytes)\n\
+             ==========================================\n"
+        );
+        crate::debug_tree!(e);
+        let empty = IntegrationTestEnvironment::new("empty");
+        assert!(render_debug_tree(&empty).contains("==========\n(empty)\n=========="));
+    }
+
+    #[test]
+    fn empty_file_metadata() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_empty_file("empty");
+        e.setup();
+        assert_eq!(
+            e.tree_with_metadata(),
+            vec![TreeEntry {
+                path: PathBuf::from("empty"),
+                is_dir: false,
+                size: 0
+            }]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn tree_json() {
+        let e = environment();
+        let json: serde_json::Value = serde_json::from_str(&e.tree_json()).unwrap();
+        let entries: Vec<TreeEntry> = json
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| TreeEntry {
+                path: PathBuf::from(entry["path"].as_str().unwrap()),
+                is_dir: entry["is_dir"].as_bool().unwrap(),
+                size: entry["size"].as_u64().unwrap(),
+            })
+            .collect();
+        assert_eq!(entries, e.tree_with_metadata());
+        assert_eq!(e.tree_json(), environment().tree_json());
+    }
+}
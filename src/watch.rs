@@ -0,0 +1,175 @@
+use crate::IntegrationTestEnvironment;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    pub kind: ChangeKind,
+    pub path: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct TimeoutError {
+    pub path: PathBuf,
+    pub timeout: Duration,
+}
+
+impl Display for TimeoutError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "no change on {:?} within {:?}", self.path, self.timeout)
+    }
+}
+
+impl Error for TimeoutError {}
+
+impl IntegrationTestEnvironment {
+    // The nearest existing ancestor is watched, so that the path can be created later on,
+    // including by renaming a temporary file or one of its parent directories.
+    pub fn wait_for_change<P: AsRef<Path>>(
+        &self,
+        path: P,
+        timeout: Duration,
+    ) -> Result<ChangeEvent, TimeoutError> {
+        let root = self
+            .path()
+            .unwrap_or_else(|_| panic!("fail to resolve {:?}", self.tmp_dir.path()));
+        let target = root.join(path.as_ref());
+        let watched = target
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(&root)
+            .to_path_buf();
+
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(sender)
+            .unwrap_or_else(|_| panic!("fail to watch {:?}", watched));
+        watcher
+            .watch(&watched, RecursiveMode::Recursive)
+            .unwrap_or_else(|_| panic!("fail to watch {:?}", watched));
+
+        let start = Instant::now();
+        loop {
+            let remaining = timeout.saturating_sub(start.elapsed());
+            let event = match receiver.recv_timeout(remaining) {
+                Ok(Ok(event)) => event,
+                Ok(Err(error)) => panic!("fail to watch {:?}: {}", watched, error),
+                Err(_) => {
+                    return Err(TimeoutError {
+                        path: path.as_ref().to_path_buf(),
+                        timeout,
+                    })
+                }
+            };
+            for event_path in &event.paths {
+                if let Some((kind, changed)) = change(&event.kind, event_path, &target) {
+                    let path = changed
+                        .strip_prefix(&root)
+                        .unwrap_or(&changed)
+                        .to_path_buf();
+                    return Ok(ChangeEvent { kind, path });
+                }
+            }
+        }
+    }
+}
+
+fn change(kind: &EventKind, event_path: &Path, target: &Path) -> Option<(ChangeKind, PathBuf)> {
+    if event_path.starts_with(target) {
+        let kind = match kind {
+            EventKind::Create(_) => ChangeKind::Created,
+            EventKind::Remove(_) => ChangeKind::Removed,
+            EventKind::Modify(ModifyKind::Name(_)) if event_path.exists() => ChangeKind::Created,
+            EventKind::Modify(ModifyKind::Name(_)) => ChangeKind::Removed,
+            EventKind::Modify(_) => ChangeKind::Modified,
+            _ => return None,
+        };
+        return Some((kind, event_path.to_path_buf()));
+    }
+    // An ancestor appearing or going away only matters if it took the target with it.
+    if target.starts_with(event_path) {
+        return match kind {
+            EventKind::Create(_) | EventKind::Modify(ModifyKind::Name(_)) if target.exists() => {
+                Some((ChangeKind::Created, target.to_path_buf()))
+            }
+            EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_)) if !target.exists() => {
+                Some((ChangeKind::Removed, target.to_path_buf()))
+            }
+            _ => None,
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ChangeEvent, ChangeKind, IntegrationTestEnvironment};
+    use std::fs::{create_dir_all, rename, write};
+    use std::path::PathBuf;
+    use std::thread::{sleep, spawn};
+    use std::time::Duration;
+
+    #[test]
+    fn wait_for_atomic_rename() {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_dir("out");
+        e.setup();
+        let out = e.path().unwrap().join("out");
+        let writer = spawn(move || {
+            sleep(Duration::from_millis(100));
+            write(out.join("result.json.tmp"), "{}").unwrap();
+            rename(out.join("result.json.tmp"), out.join("result.json")).unwrap();
+        });
+        let event = e
+            .wait_for_change("out/result.json", Duration::from_secs(10))
+            .unwrap();
+        writer.join().unwrap();
+        assert_eq!(
+            event,
+            ChangeEvent {
+                kind: ChangeKind::Created,
+                path: PathBuf::from("out/result.json")
+            }
+        );
+    }
+
+    #[test]
+    fn wait_for_parent_rename() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.setup();
+        let root = e.path().unwrap();
+        let writer = spawn(move || {
+            sleep(Duration::from_millis(100));
+            create_dir_all(root.join("out.tmp")).unwrap();
+            write(root.join("out.tmp/result.json"), "{}").unwrap();
+            rename(root.join("out.tmp"), root.join("out")).unwrap();
+        });
+        let event = e
+            .wait_for_change("out/result.json", Duration::from_secs(10))
+            .unwrap();
+        writer.join().unwrap();
+        assert_eq!(event.kind, ChangeKind::Created);
+        assert_eq!(event.path, PathBuf::from("out/result.json"));
+    }
+
+    #[test]
+    fn wait_for_change_timeout() {
+        let e = IntegrationTestEnvironment::new("test");
+        e.setup();
+        let error = e
+            .wait_for_change("never", Duration::from_millis(50))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "no change on \"never\" within 50ms");
+    }
+}
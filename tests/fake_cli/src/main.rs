@@ -0,0 +1,135 @@
+use std::env;
+use std::process::exit;
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    // Lets a test pass only parameters, the subcommand comes from the environment.
+    if let Ok(subcommand) = env::var("FAKE_CLI_SUBCOMMAND") {
+        args.insert(0, subcommand);
+    }
+    match args.first().map(String::as_str) {
+        Some("args") => {
+            for arg in &args[1..] {
+                println!("{}", arg);
+            }
+        }
+        Some("cwd") => {
+            println!("{}", env::current_dir().unwrap().display());
+        }
+        Some("env") => {
+            for name in &args[1..] {
+                println!("{}={}", name, env::var(name).unwrap_or_default());
+            }
+        }
+        Some("write_in_var_dir") => {
+            let dir = env::var(&args[1]).expect("directory variable is not set");
+            let path = std::path::Path::new(&dir).join(&args[2]);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, &args[3]).unwrap();
+        }
+        Some("append") => {
+            use std::io::Write;
+            let mut file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&args[1])
+                .expect("fail to open file");
+            writeln!(file, "{}", args[2]).unwrap();
+        }
+        // Exits with 42 on SIGTERM, the ready file is written once the handler is installed.
+        #[cfg(unix)]
+        Some("trap_term") => {
+            extern "C" fn on_term(_: libc::c_int) {
+                unsafe { libc::_exit(42) };
+            }
+            unsafe {
+                libc::signal(
+                    libc::SIGTERM,
+                    on_term as extern "C" fn(libc::c_int) as libc::sighandler_t,
+                )
+            };
+            std::fs::write(&args[1], "").unwrap();
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+        // Ignores SIGTERM, only SIGKILL stops it.
+        #[cfg(unix)]
+        Some("ignore_term") => {
+            unsafe { libc::signal(libc::SIGTERM, libc::SIG_IGN) };
+            std::fs::write(&args[1], "").unwrap();
+            loop {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+        Some("echo_stdin") => {
+            use std::io::{Read, Write};
+            let mut stdin = vec![];
+            std::io::stdin().read_to_end(&mut stdin).unwrap();
+            std::io::stdout().write_all(&stdin).unwrap();
+        }
+        // Prompts without a trailing newline, each answer is needed for the next prompt.
+        Some("wizard") => {
+            use std::io::{BufRead, Write};
+            let mut lines = std::io::stdin().lock().lines();
+            print!("name? ");
+            std::io::stdout().flush().unwrap();
+            let name = lines.next().unwrap().unwrap();
+            print!("color for {}? ", name);
+            std::io::stdout().flush().unwrap();
+            let color = lines.next().unwrap().unwrap();
+            println!("saved {} {}", name, color);
+            eprintln!("done");
+        }
+        Some("sleep") => {
+            let millis = args[1].parse().expect("sleep duration must be an integer");
+            std::thread::sleep(std::time::Duration::from_millis(millis));
+            for arg in &args[2..] {
+                println!("{}", arg);
+            }
+        }
+        Some("write_after") => {
+            let millis = args[1].parse().expect("sleep duration must be an integer");
+            std::thread::sleep(std::time::Duration::from_millis(millis));
+            let tmp = format!("{}.tmp", args[2]);
+            std::fs::write(&tmp, &args[3]).unwrap();
+            std::fs::rename(tmp, &args[2]).unwrap();
+        }
+        Some("number_lines") => {
+            let mut stdin = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut stdin).unwrap();
+            for (index, line) in stdin.lines().enumerate() {
+                println!("{}: {}", index + 1, line);
+            }
+        }
+        Some("warn") => {
+            eprintln!("starting");
+            eprintln!("[WARN] 2024-01-01T00:00:00Z {}", args[1..].join(" "));
+        }
+        Some("exit") => {
+            let code = args[1].parse().expect("exit code must be an integer");
+            println!("stdout before exit {}", code);
+            eprintln!("stderr before exit {}", code);
+            exit(code);
+        }
+        Some("abort") => std::process::abort(),
+        #[cfg(feature = "sqlite")]
+        Some("sqlite") => {
+            let connection = rusqlite::Connection::open(&args[1]).expect("fail to open database");
+            connection
+                .execute_batch(&args[2])
+                .expect("fail to execute sql");
+        }
+        Some("run") => {
+            let status = std::process::Command::new(&args[1])
+                .args(&args[2..])
+                .status()
+                .expect("fail to run program");
+            exit(status.code().unwrap_or(1));
+        }
+        _ => {
+            eprintln!("usage: fake_cli <args|cwd|env|write_in_var_dir|append|echo_stdin|wizard|trap_term|ignore_term|sleep|write_after|number_lines|warn|exit|abort|sqlite|run> ...");
+            exit(2);
+        }
+    }
+}
@@ -0,0 +1,819 @@
+use cli_integration_test::{
+    assert_output, println_output, println_result_output, CargoBinError,
+    IntegrationTestEnvironment, OutputExt,
+};
+use predicates::str::contains;
+use std::io;
+use std::time::Duration;
+
+#[test]
+fn command_callback_receives_crate_name() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_command_callback(|crate_name, root, mut command| {
+        if crate_name == "fake_cli" {
+            command.arg("args").arg("--root").arg(root);
+        }
+        command
+    });
+    e.setup();
+    let root = e.path().unwrap();
+    e.command("fake_cli")
+        .unwrap()
+        .assert()
+        .success()
+        .stdout(format!("--root\n{}\n", root.display()));
+}
+
+#[test]
+fn legacy_command_callback() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_cfg_command_callback(|root, mut command| {
+        command.arg("args").arg(root);
+        command
+    });
+    e.setup();
+    let root = e.path().unwrap();
+    e.command("fake_cli")
+        .unwrap()
+        .assert()
+        .success()
+        .stdout(contains(root.to_string_lossy().to_string()));
+}
+
+#[test]
+fn ready_env() {
+    let e = IntegrationTestEnvironment::builder("test")
+        .file("input", "staged")
+        .env("READY", "yes")
+        .build();
+    e.command_args("fake_cli", &["env", "READY"])
+        .assert()
+        .success()
+        .stdout("READY=yes\n");
+    assert_eq!(e.read_file("input"), "staged");
+}
+
+#[test]
+fn command_args() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.command_args("fake_cli", &["args", "first", "second arg"])
+        .assert()
+        .success()
+        .stdout("first\nsecond arg\n");
+}
+
+#[test]
+fn isolate_home() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    let home = e.isolate_home();
+    e.set_env("FROM_SET_ENV", "yes");
+    e.add_file("home/.config/tool/config.toml", "key = 1");
+    e.set_command_callback(|_, _, mut command| {
+        command.env("FROM_CALLBACK", "yes");
+        command
+    });
+    e.setup();
+    assert!(home.join(".config/tool/config.toml").is_file());
+    e.command_args(
+        "fake_cli",
+        &["env", "HOME", "FROM_CALLBACK", "FROM_SET_ENV"],
+    )
+    .assert()
+    .success()
+    .stdout(format!(
+        "HOME={}\nFROM_CALLBACK=yes\nFROM_SET_ENV=yes\n",
+        home.display()
+    ));
+}
+
+#[test]
+#[cfg(unix)]
+fn prepend_path() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.add_file("bin/helper", "#!/bin/sh\necho \"helper $1\"\n");
+    e.prepend_path("bin");
+    e.setup();
+    e.make_executable("bin/helper").unwrap();
+    e.command_args("fake_cli", &["run", "helper", "called"])
+        .assert()
+        .success()
+        .stdout("helper called\n");
+
+    let path = e
+        .command_args("fake_cli", &["env", "PATH"])
+        .output()
+        .unwrap();
+    let path = String::from_utf8(path.stdout).unwrap();
+    let bin = e.path().unwrap().join("bin");
+    assert!(path.starts_with(&format!("PATH={}:", bin.display())));
+    assert!(path.trim_end().ends_with(&std::env::var("PATH").unwrap()));
+}
+
+#[test]
+#[cfg(unix)]
+fn path_modes() {
+    use cli_integration_test::PathMode;
+    let run_true = |mode| {
+        let mut e = IntegrationTestEnvironment::new("test");
+        e.add_file("bin/true", "#!/bin/sh\necho sandbox\n");
+        e.set_path_dir("bin", mode);
+        e.setup();
+        e.make_executable("bin/true").unwrap();
+        e.command_args("fake_cli", &["run", "true"])
+            .output()
+            .unwrap()
+    };
+    assert_eq!(run_true(PathMode::Prepend).stdout_str(), "sandbox\n");
+    assert_eq!(run_true(PathMode::Replace).stdout_str(), "sandbox\n");
+    let appended = run_true(PathMode::Append);
+    assert!(appended.success());
+    assert_eq!(appended.stdout_str(), "");
+
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.add_dir("bin");
+    e.set_path_dir("bin", PathMode::Replace);
+    e.setup();
+    let path = e
+        .command_args("fake_cli", &["env", "PATH"])
+        .output()
+        .unwrap();
+    let bin = e.temp_dir().path().join("bin");
+    assert_eq!(path.stdout_str(), format!("PATH={}\n", bin.display()));
+    assert!(!e
+        .command_args("fake_cli", &["run", "ls"])
+        .output()
+        .unwrap()
+        .success());
+}
+
+#[test]
+#[cfg(unix)]
+fn environment_macro() {
+    let mut e = cli_integration_test::environment!("test", {
+        "bin/helper" => exec "#!/bin/sh\necho from macro\n",
+        "data/" => dir,
+    });
+    e.prepend_path("bin");
+    e.setup();
+    assert!(e.file_exists("data"));
+    e.command_args("fake_cli", &["run", "helper"])
+        .assert()
+        .success()
+        .stdout("from macro\n");
+}
+
+#[test]
+fn isolate_xdg() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    let home = e.isolate_home();
+    let xdg = e.isolate_xdg();
+    e.setup();
+    let root = home.parent().unwrap().to_path_buf();
+    e.command_args(
+        "fake_cli",
+        &[
+            "env",
+            "HOME",
+            "XDG_CONFIG_HOME",
+            "XDG_DATA_HOME",
+            "XDG_CACHE_HOME",
+        ],
+    )
+    .assert()
+    .success()
+    .stdout(format!(
+        "HOME={}\nXDG_CONFIG_HOME={}\nXDG_DATA_HOME={}\nXDG_CACHE_HOME={}\n",
+        home.display(),
+        root.join(xdg.config()).display(),
+        root.join(xdg.data()).display(),
+        root.join(xdg.cache()).display()
+    ));
+
+    e.command_args(
+        "fake_cli",
+        &[
+            "write_in_var_dir",
+            "XDG_CACHE_HOME",
+            "tool/cache.txt",
+            "cached",
+        ],
+    )
+    .assert()
+    .success();
+    assert_eq!(e.read_file(xdg.cache().join("tool/cache.txt")), "cached");
+    assert_eq!(e.read_file("xdg/cache/tool/cache.txt"), "cached");
+}
+
+#[test]
+#[cfg(feature = "regex")]
+fn assert_stderr_matches() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_stderr_matches(
+        "fake_cli",
+        &["warn", "option --old is deprecated"],
+        r"^\[WARN\] .* deprecated$",
+    );
+}
+
+#[test]
+#[cfg(feature = "regex")]
+#[should_panic(
+    expected = "stderr does not match \"^\\\\[ERROR\\\\]\"\n---------------------------\nstarting\n"
+)]
+fn assert_stderr_matches_mismatch() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_stderr_matches("fake_cli", &["warn", "message"], r"^\[ERROR\]");
+}
+
+#[test]
+fn assert_no_stderr() {
+    let e = IntegrationTestEnvironment::new("test");
+    let outcome = e.assert_no_stderr("fake_cli", &["args", "quiet"]);
+    assert!(outcome.status.success());
+}
+
+#[test]
+#[should_panic(
+    expected = "$ fake_cli warn deprecated\nexpected no stderr, found 48 bytes\n---------------------------\nstarting\n[WARN]"
+)]
+fn assert_no_stderr_warning() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_no_stderr("fake_cli", &["warn", "deprecated"]);
+}
+
+#[test]
+fn assert_files_eq_after_command() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.add_file("expected.txt", "round trip\n");
+    e.setup();
+    e.run("fake_cli", &["append", "written.txt", "round trip"]);
+    e.assert_files_eq("written.txt", "expected.txt");
+}
+
+#[test]
+fn fallible_command_callback() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_cfg_command_callback_result(|path, mut command| {
+        if !path.join("config.toml").exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "config.toml is missing",
+            ));
+        }
+        command.arg("args");
+        Ok(command)
+    });
+    e.setup();
+    match e.try_command("fake_cli") {
+        Err(CargoBinError::Io(error)) => assert_eq!(error.to_string(), "config.toml is missing"),
+        _ => panic!("expected the callback error"),
+    }
+    assert_eq!(
+        e.command("fake_cli").unwrap_err().kind(),
+        io::ErrorKind::NotFound
+    );
+
+    e.add_file("config.toml", "");
+    e.setup();
+    let output = e
+        .try_command("fake_cli")
+        .unwrap()
+        .arg("ok")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn assert_code() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_code("fake_cli", &["exit", "0"], 0);
+    e.assert_code("fake_cli", &["exit", "3"], 3);
+}
+
+#[test]
+#[should_panic(expected = "expected exit code 4, exit code 3")]
+fn assert_code_mismatch() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_code("fake_cli", &["exit", "3"], 4);
+}
+
+#[test]
+#[cfg(unix)]
+#[should_panic(expected = "expected exit code 0, terminated by signal")]
+fn assert_code_signal() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_code("fake_cli", &["abort"], 0);
+}
+
+#[test]
+#[cfg(feature = "http")]
+fn http_server_url_is_injected() {
+    use cli_integration_test::HTTP_URL_ENV;
+
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.setup();
+    let server = e.start_http_server();
+    e.command("fake_cli")
+        .unwrap()
+        .args(["env", HTTP_URL_ENV])
+        .assert()
+        .success()
+        .stdout(format!("{}={}\n", HTTP_URL_ENV, server.url()));
+}
+
+#[test]
+#[cfg(feature = "sqlite")]
+fn sqlite_db_written_by_command() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.add_sqlite_db(
+        "app.db",
+        "CREATE TABLE users (name TEXT NOT NULL);
+         INSERT INTO users VALUES ('alice'), ('bob');",
+    );
+    e.setup();
+    e.command("fake_cli")
+        .unwrap()
+        .args(["sqlite", "app.db", "INSERT INTO users VALUES ('carol');"])
+        .assert()
+        .success();
+    assert_eq!(
+        e.query_sqlite("app.db", "SELECT name FROM users ORDER BY rowid"),
+        vec![vec!["alice"], vec!["bob"], vec!["carol"]]
+    );
+}
+
+#[test]
+fn spawn() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_env("SPAWNED", "yes");
+    e.setup();
+    let mut slow = e.spawn("fake_cli", &["sleep", "200"]).unwrap();
+    let mut fast = e.spawn("fake_cli", &["env", "SPAWNED"]).unwrap();
+    assert!(fast.wait().unwrap().success());
+    assert!(slow.wait().unwrap().success());
+}
+
+#[test]
+fn spawn_applies_command_callback() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.setup();
+    let out_dir = e.path().unwrap();
+    e.set_command_callback(move |_, _, mut command| {
+        command.arg("write_in_var_dir").env("OUT_DIR", &out_dir);
+        command
+    });
+    let mut child = e
+        .spawn("fake_cli", &["OUT_DIR", "spawned.txt", "spawned"])
+        .unwrap();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(e.read_file("spawned.txt"), "spawned");
+
+    let background = e
+        .spawn_background("fake_cli", &["OUT_DIR", "background.txt", "background"])
+        .unwrap();
+    assert!(background.wait().unwrap().success());
+    assert_eq!(e.read_file("background.txt"), "background");
+}
+
+#[test]
+fn interact_applies_command_callback() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_command_callback(|_, _, mut command| {
+        command
+            .args(["env", "FROM_CALLBACK"])
+            .env("FROM_CALLBACK", "yes");
+        command
+    });
+    let mut session = e.interact("fake_cli");
+    session.expect("FROM_CALLBACK=yes", Duration::from_secs(10));
+    assert!(session.finish().success());
+}
+
+#[test]
+fn run_with_stdin_file() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.add_file("fixtures/input.txt", "first\nsecond\n");
+    e.setup();
+    let outcome = e.run_with_stdin_file("fake_cli", &["number_lines"], "fixtures/input.txt");
+    assert!(outcome.status.success());
+    assert_eq!(outcome.stdout_str(), "1: first\n2: second\n");
+}
+
+#[test]
+fn run_timed() {
+    let e = IntegrationTestEnvironment::new("test");
+    let (outcome, elapsed) = e.run_timed("fake_cli", &["sleep", "50", "done"]);
+    assert!(outcome.status.success());
+    assert_eq!(outcome.stdout_str(), "done\n");
+    assert!(elapsed >= Duration::from_millis(50));
+}
+
+#[test]
+fn timed_run() {
+    let e = IntegrationTestEnvironment::new("test");
+    let command = e.command_args("fake_cli", &["sleep", "50", "done"]);
+    let (output, elapsed) = e.timed_run(command);
+    assert_eq!(output.stdout_str(), "done\n");
+    assert!(elapsed >= Duration::from_millis(50));
+    assert!(e
+        .format_last_output(&output)
+        .starts_with(&format!("$ fake_cli sleep 50 done (took {:?})\n", elapsed)));
+
+    let mut command = e.command("fake_cli").unwrap();
+    command.args(["args", "two words"]);
+    e.command_args("fake_cli", &["args", "not run"]);
+    let (output, elapsed) = e.timed_run(command);
+    assert!(e.format_last_output(&output).starts_with(&format!(
+        "$ fake_cli args 'two words' (took {:?})\n",
+        elapsed
+    )));
+}
+
+#[test]
+fn wait_for_file() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.setup();
+    let mut child = e
+        .spawn("fake_cli", &["write_after", "100", "ready", "done"])
+        .unwrap();
+    assert!(!e.file_exists("ready"));
+    e.wait_for_file("ready", Duration::from_secs(10)).unwrap();
+    assert_eq!(e.read_file("ready"), "done");
+    assert!(child.wait().unwrap().success());
+}
+
+#[test]
+fn println_macros() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e.command_args("fake_cli", &["exit", "1"]).output().unwrap();
+    println_output!(output);
+    let result = e.command_args("fake_cli", &["exit", "1"]).ok();
+    println_result_output!(result);
+}
+
+#[test]
+fn assert_output_macro() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e
+        .command_args("fake_cli", &["args", "a", "b"])
+        .output()
+        .unwrap();
+    assert_output!(output, "a\nb");
+    assert_output!(output, contains "b");
+    assert_output!(output, matches contains("a\n"));
+}
+
+#[test]
+#[should_panic(expected = "-c\n+b\n")]
+fn assert_output_mismatch() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e
+        .command_args("fake_cli", &["args", "a", "b"])
+        .output()
+        .unwrap();
+    assert_output!(output, "a\nc\n");
+}
+
+#[test]
+fn cwd_is_canonical_root() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e.command_args("fake_cli", &["cwd"]).output().unwrap();
+    assert_eq!(
+        output.stdout_str().trim_end(),
+        e.canonical_path().display().to_string()
+    );
+    assert_eq!(e.strip_root(&output.stdout_str()), "[ROOT]\n");
+}
+
+#[test]
+fn command_with_args_records_invocation() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e
+        .command_with_args("fake_cli", ["args", "two words", "it's", "plain"])
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout_str(), "two words\nit's\nplain\n");
+    assert_eq!(
+        e.last_invocation().as_deref(),
+        Some("fake_cli args 'two words' 'it'\\''s' plain")
+    );
+    assert!(e
+        .format_last_output(&output)
+        .starts_with("$ fake_cli args 'two words' 'it'\\''s' plain\n[stderr: 0 bytes]\n"));
+}
+
+#[test]
+#[should_panic(expected = "$ fake_cli exit 3\nexpected exit code 4, exit code 3")]
+fn assert_code_reports_invocation() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.assert_code("fake_cli", &["exit", "3"], 4);
+}
+
+#[test]
+fn assert_exit_code() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e.command_args("fake_cli", &["exit", "3"]).output().unwrap();
+    e.assert_exit_code(&output, 3);
+    let output = e.command_args("fake_cli", &["exit", "0"]).output().unwrap();
+    e.assert_success(&output);
+}
+
+#[test]
+#[should_panic(
+    expected = "assertion failed on \"exit\": expected exit code 4, exit code 3\n$ fake_cli exit 3\ntree:\n  config.toml\n[stderr: 21 bytes]\nstderr before exit 3\n"
+)]
+fn assert_exit_code_mismatch() {
+    let mut e = IntegrationTestEnvironment::new("exit");
+    e.add_file("config.toml", "");
+    e.setup();
+    let output = e.command_args("fake_cli", &["exit", "3"]).output().unwrap();
+    e.assert_exit_code(&output, 4);
+}
+
+#[test]
+#[cfg(unix)]
+#[should_panic(expected = "expected success, terminated by signal 6")]
+fn assert_success_signal() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e.command_args("fake_cli", &["abort"]).output().unwrap();
+    e.assert_success(&output);
+}
+
+#[test]
+fn history() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_env("MODE", "test");
+    e.enable_history();
+    e.run("fake_cli", &["args", "first"]);
+    e.run("fake_cli", &["exit", "3"]);
+    e.run("fake_cli", &["args", "third"]);
+
+    let history = e.history();
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[1].status.code(), Some(3));
+    assert_eq!(history[1].args, vec!["exit", "3"]);
+    assert_eq!(history[1].cwd, e.canonical_path());
+    assert_eq!(
+        history[1].envs,
+        vec![("MODE".to_string(), "test".to_string())]
+    );
+    assert_eq!(history[2].stdout, b"third\n");
+    drop(history);
+
+    let transcript = e.format_history();
+    assert!(transcript.starts_with("[0] $ fake_cli args first ("));
+    assert!(transcript.contains("===========================\n[1] $ fake_cli exit 3 ("));
+    assert!(transcript.contains("[2] $ fake_cli args third ("));
+}
+
+#[test]
+fn history_is_opt_in() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.run("fake_cli", &["exit", "0"]);
+    assert!(e.history().is_empty());
+}
+
+#[test]
+fn command_with_path_args() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.add_file("in/data.csv", "");
+    e.set_env("FAKE_CLI_SUBCOMMAND", "args");
+    e.setup();
+    let output = e
+        .command_with_path_args("fake_cli", &["in/data.csv", "./out"])
+        .output()
+        .unwrap();
+    let root = e.canonical_path();
+    assert_eq!(
+        output.stdout_lines(),
+        vec![
+            root.join("in/data.csv").display().to_string(),
+            root.join("out").display().to_string(),
+        ]
+    );
+}
+
+#[test]
+fn run_concurrently() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.setup();
+    let outputs = e.run_concurrently(2, |index| {
+        e.command_args("fake_cli", &["append", "log", &format!("run {}", index)])
+    });
+    assert_eq!(outputs.len(), 2);
+    assert!(outputs.iter().all(|output| output.status.success()));
+    let mut lines = e.read_lines("log");
+    lines.sort();
+    assert_eq!(lines, vec!["run 0", "run 1"]);
+}
+
+#[test]
+fn run_concurrently_timeout() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_concurrent_timeout(Duration::from_millis(200));
+    let outputs = e.run_concurrently(2, |index| match index {
+        0 => e.command_args("fake_cli", &["sleep", "10000"]),
+        _ => e.command_args("fake_cli", &["args", "fast"]),
+    });
+    assert!(!outputs[0].status.success());
+    assert_eq!(outputs[1].stdout_str(), "fast\n");
+}
+
+#[test]
+fn run_concurrently_collects_every_child() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_panic_on_error(false);
+    let outputs = e.run_concurrently(3, |index| match index {
+        1 => assert_cmd::Command::new(e.path().unwrap().join("missing")),
+        _ => e.command_args("fake_cli", &["args", &format!("run {}", index)]),
+    });
+    assert_eq!(outputs[0].stdout_str(), "run 0\n");
+    assert!(!outputs[1].status.success());
+    assert_eq!(outputs[2].stdout_str(), "run 2\n");
+    let errors = e.take_errors();
+    assert_eq!(errors.len(), 1);
+    assert!(
+        errors[0].starts_with("fail to run command 1: "),
+        "{}",
+        errors[0]
+    );
+}
+
+#[test]
+#[should_panic(expected = "fail to run command 0: ")]
+fn run_concurrently_panics_on_error() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.run_concurrently(1, |_| {
+        assert_cmd::Command::new(e.path().unwrap().join("missing"))
+    });
+}
+
+#[test]
+#[cfg(unix)]
+fn terminate_gracefully() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.setup();
+    let process = e
+        .spawn_background("fake_cli", &["trap_term", "ready"])
+        .unwrap();
+    e.wait_for_file("ready", Duration::from_secs(10)).unwrap();
+    let status = process
+        .terminate_gracefully(Duration::from_secs(10))
+        .unwrap();
+    assert_eq!(status.code(), Some(42));
+}
+
+#[test]
+#[cfg(unix)]
+fn terminate_gracefully_kills_after_grace() {
+    use std::os::unix::process::ExitStatusExt;
+    let e = IntegrationTestEnvironment::new("test");
+    e.setup();
+    let process = e
+        .spawn_background("fake_cli", &["ignore_term", "ready"])
+        .unwrap();
+    e.wait_for_file("ready", Duration::from_secs(10)).unwrap();
+    let status = process
+        .terminate_gracefully(Duration::from_millis(100))
+        .unwrap();
+    assert_eq!(status.signal(), Some(9));
+}
+
+#[test]
+#[cfg(windows)]
+fn signal_unsupported() {
+    let e = IntegrationTestEnvironment::new("test");
+    let process = e.spawn_background("fake_cli", &["sleep", "10000"]).unwrap();
+    let error = process.signal(15).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::Unsupported);
+}
+
+#[test]
+fn command_with_stdin() {
+    let e = IntegrationTestEnvironment::new("test");
+    let mut command = e.command_with_stdin("fake_cli", "b\na\n");
+    let output = command.arg("number_lines").output().unwrap();
+    assert_eq!(output.stdout_str(), "1: b\n2: a\n");
+
+    let output = e
+        .command_with_stdin("fake_cli", Vec::new())
+        .arg("echo_stdin")
+        .output()
+        .unwrap();
+    assert!(output.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn run_with_stdin() {
+    let e = IntegrationTestEnvironment::new("test");
+    let binary = vec![0xff, 0x00, 0xfe, b'\n'];
+    let output = e.run_with_stdin(e.command_args("fake_cli", &["echo_stdin"]), binary.clone());
+    assert_eq!(output.stdout, binary);
+
+    let large: Vec<u8> = (0..20 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let output = e.run_with_stdin(e.command_args("fake_cli", &["echo_stdin"]), large.clone());
+    assert!(output.success());
+    assert_eq!(output.stdout.len(), large.len());
+    assert!(output.stdout == large);
+}
+
+#[test]
+fn interact() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_env("FAKE_CLI_SUBCOMMAND", "wizard");
+    let mut session = e.interact("fake_cli");
+    session.expect("name? ", Duration::from_secs(10));
+    session.send_line("box");
+    session.expect("color for box? ", Duration::from_secs(10));
+    session.send_line("red");
+    #[cfg(feature = "regex")]
+    session.expect_regex(
+        &regex::Regex::new(r"saved \w+ red").unwrap(),
+        Duration::from_secs(10),
+    );
+    #[cfg(not(feature = "regex"))]
+    session.expect("saved box red", Duration::from_secs(10));
+    let output = session.finish();
+    assert!(output.success());
+    assert_eq!(output.stdout_str(), "name? color for box? saved box red\n");
+    assert_eq!(output.stderr_str(), "done\n");
+}
+
+#[test]
+#[should_panic(
+    expected = "fail to find \"color? \" within 100ms\n---------------------------\nname? \n"
+)]
+fn interact_timeout() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_env("FAKE_CLI_SUBCOMMAND", "wizard");
+    let mut session = e.interact("fake_cli");
+    session.expect("name? ", Duration::from_secs(10));
+    session.expect("color? ", Duration::from_millis(100));
+}
+
+#[test]
+fn configure_command() {
+    let mut e = IntegrationTestEnvironment::new("test");
+    e.set_env("MODE", "configured");
+    e.set_command_callback(|_, _, mut command| {
+        command.env("FROM_CALLBACK", "yes");
+        command
+    });
+    let mut command = assert_cmd::Command::cargo_bin("fake_cli").unwrap();
+    command.arg("cwd");
+    let output = e.configure_command("fake_cli", command).output().unwrap();
+    assert_eq!(
+        output.stdout_str().trim_end(),
+        e.canonical_path().display().to_string()
+    );
+
+    let mut command = assert_cmd::Command::cargo_bin("fake_cli").unwrap();
+    command.args(["env", "MODE", "FROM_CALLBACK"]);
+    let output = e.configure_command("fake_cli", command).output().unwrap();
+    assert_eq!(output.stdout_str(), "MODE=configured\nFROM_CALLBACK=yes\n");
+}
+
+#[test]
+fn command_example() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e.command_example("demo").output().unwrap();
+    assert_eq!(
+        output.stdout_str(),
+        format!("demo {}\n", e.canonical_path().display())
+    );
+}
+
+#[test]
+fn command_bin_of() {
+    let e = IntegrationTestEnvironment::new("test");
+    let output = e
+        .command_bin_of("cli_integration_test", "fake_cli")
+        .args(["args", "of package"])
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout_str(), "of package\n");
+}
+
+#[test]
+#[should_panic(expected = "build it with `cargo build -p other --bin missing_bin`")]
+fn command_bin_of_missing() {
+    let e = IntegrationTestEnvironment::new("test");
+    e.command_bin_of("other", "missing_bin");
+}
+
+#[test]
+fn bin_path_is_cached() {
+    let e = IntegrationTestEnvironment::new("test");
+    let path = e.bin_path("fake_cli").unwrap();
+    for _ in 0..40 {
+        e.command("fake_cli").unwrap();
+        assert_eq!(e.bin_path("fake_cli").unwrap(), path);
+    }
+    e.clear_command_cache();
+    assert_eq!(e.bin_path("fake_cli").unwrap(), path);
+    e.command_args("fake_cli", &["exit", "0"])
+        .assert()
+        .success();
+}